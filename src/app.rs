@@ -1,138 +1,325 @@
-use pollster::FutureExt;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::Duration;
 use eframe::egui;
 use eframe::egui::{Align, Color32, Frame, Layout, Margin, RichText, Vec2, Window};
 use eframe::epaint::Stroke;
 use eframe::glow::Context;
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::task;
-use tokio::sync::oneshot::{self, Receiver as OnceReceiver};
-use tracing::debug;
-use windows_bluetooth::{connect_to_device_os, discover_devices, remove_device, BluetoothDevice, ConnectToDeviceError, DiscoverDevicesError, MacAddress, RemoveDeviceError};
+use tokio::sync::mpsc::{Receiver, UnboundedSender};
+use tracing::{debug, info};
+use windows_bluetooth::{AsyncBluetoothBackend, BackendTask, BluetoothBackend, BluetoothDevice, BluetoothError, DeviceEvent, DeviceWatcher, DiscoverOptions, ErrorKind, MacAddress, SearchTask};
 use crate::card::{AvailableDeviceCard, ConnectedDeviceCard};
 use crate::editing::{TimeoutEditUi, TimeoutEditing};
 use crate::logging::Log;
-use crate::notification::Notification;
+use crate::notification::{Notification, WarningChoice, WarningNotification};
+use crate::pairing::PairingDialog;
+use crate::profiles::Profiles;
+use crate::scheduler::{TimeoutEvent, TimeoutScheduler};
 use crate::spinner::RescanButtonSpinner;
-use crate::timeout::Timeout;
+use crate::timeout::{ExpiryAction, Timeout};
 
-pub type RemoveDeviceRes = Result<(), RemoveDeviceError>;
-pub type ConnectToDeviceRes = Result<(), ConnectToDeviceError>;
+pub type RemoveDeviceRes = Result<(), BluetoothError>;
+pub type DisconnectDeviceRes = Result<(), BluetoothError>;
+pub type ConnectToDeviceRes = Result<(), BluetoothError>;
+pub type GetDeviceRes = Result<BluetoothDevice, BluetoothError>;
+pub type SharedBackend = Arc<dyn BluetoothBackend>;
+/// The operation the scheduler started for a device which timed out
+pub type ExpiredTask = (MacAddress, ExpiryAction, BackendTask<Result<(), BluetoothError>>);
+/// An operation started as the app closes, which is awaited after the window is gone
+pub type ExitTask = BackendTask<Result<(), BluetoothError>>;
+
+/// How long a deep scan searches for devices in range, which is about as long as Windows' own search
+pub const DEEP_SCAN_DURATION: Duration = Duration::from_millis(1280 * 8);
 
 pub struct BluetoothApp {
-    pub devices: Vec<BluetoothDevice>,
+    pub backend: SharedBackend,
+    pub watcher: DeviceWatcher,
     pub timeouts: Vec<Timeout>,
-    pub connect_res_channel: (Sender<ConnectToDeviceRes>, Receiver<ConnectToDeviceRes>),
-    pub remove_res_channel: (Sender<RemoveDeviceRes>, Receiver<RemoveDeviceRes>),
-    pub scan_recv: Option<OnceReceiver<Result<Vec<BluetoothDevice>, DiscoverDevicesError>>>,
-    pub last_timeout_update: Instant,
+    pub scheduler: TimeoutScheduler,
+    /// Remembered timeouts, which devices start with when they connect
+    pub profiles: Profiles,
+    pub expired_rx: mpsc::Receiver<ExpiredTask>,
+    pub warning_rx: mpsc::Receiver<MacAddress>,
+    /// Devices about to time out, whose warnings are shown until they're extended or expire
+    pub warnings: Vec<MacAddress>,
+    pub connect_tasks: Vec<(MacAddress, BackendTask<ConnectToDeviceRes>)>,
+    pub disconnect_tasks: Vec<(MacAddress, BackendTask<DisconnectDeviceRes>)>,
+    pub remove_tasks: Vec<(MacAddress, BackendTask<RemoveDeviceRes>)>,
+    /// Lookups of single devices whose state an operation changed
+    pub refresh_tasks: Vec<(MacAddress, BackendTask<GetDeviceRes>)>,
+    pub scan_task: Option<SearchTask>,
+    /// Devices found so far by the running scan
+    pub scan_found: Vec<BluetoothDevice>,
     pub log_rx: Receiver<Log>,
     pub logs: Vec<Log>,
     pub editing: Option<TimeoutEditing>,
+    pub pairing: PairingDialog,
+    pub exit_tx: UnboundedSender<ExitTask>,
 }
 
 impl BluetoothApp {
-    pub fn new_now_with_log_rx(rx: Receiver<Log>) -> Self {
+    /// Must be called from within the tokio runtime, which the scheduler starts its operations on.
+    ///
+    /// The operations started on close are sent to `exit_tx`, for the runtime to await once the app is closed.
+    pub fn new_now_with_log_rx(backend: SharedBackend, rx: Receiver<Log>, pairing: PairingDialog, scheduler: TimeoutScheduler, profiles: Profiles, exit_tx: UnboundedSender<ExitTask>, ctx: egui::Context) -> Self {
+        let (expired_tx, expired_rx) = mpsc::channel();
+        let (warning_tx, warning_rx) = mpsc::channel();
+
+        let runtime = tokio::runtime::Handle::current();
+        let expiry_backend = backend.clone();
+
+        // starts the operation right away, so a device times out even while the window isn't repainting
+        scheduler.spawn(move |event| {
+            match event {
+                TimeoutEvent::Warning { mac_address, .. } => {
+                    let _ = warning_tx.send(mac_address);
+                }
+                TimeoutEvent::Expired { mac_address, action } => {
+                    let _guard = runtime.enter();
+
+                    let task = match action {
+                        ExpiryAction::Disconnect => expiry_backend.disconnect_device_async(mac_address),
+                        ExpiryAction::Remove => expiry_backend.remove_device_async(mac_address),
+                    };
+
+                    let _ = expired_tx.send((mac_address, action, task));
+                }
+            }
+
+            ctx.request_repaint();
+        });
+
         Self {
-            devices: Vec::default(),
+            backend,
+            watcher: DeviceWatcher::new(),
             timeouts: Vec::default(),
-            connect_res_channel: mpsc::channel(2),
-            remove_res_channel: mpsc::channel(2),
-            scan_recv: None,
-            last_timeout_update: Instant::now(),
+            scheduler,
+            profiles,
+            expired_rx,
+            warning_rx,
+            warnings: Vec::new(),
+            connect_tasks: Vec::new(),
+            disconnect_tasks: Vec::new(),
+            remove_tasks: Vec::new(),
+            refresh_tasks: Vec::new(),
+            scan_task: None,
+            scan_found: Vec::new(),
             log_rx: rx,
             logs: Vec::new(),
             editing: None,
+            pairing,
+            exit_tx,
         }
     }
 
     pub fn start_scan(&mut self) {
         debug!("Scanning for bluetooth devices");
-        let (tx, rx) = oneshot::channel();
-
-        task::spawn_blocking(move ||
-            tx.send(discover_devices()).expect("should be able to send")
-        );
 
-        self.scan_recv = Some(rx);
+        self.search(DiscoverOptions::new());
     }
 
-    pub fn start_connect_with_tx(tx: Sender<ConnectToDeviceRes>, mac_address: MacAddress) {
-        task::spawn_blocking(move || tx.blocking_send(connect_to_device_os(mac_address)));
+    /// Scans while also searching for devices in range, which finds devices that haven't been paired yet.
+    pub fn start_deep_scan(&mut self) {
+        debug!("Searching for bluetooth devices in range");
+
+        self.search(DiscoverOptions::new().inquiry(DEEP_SCAN_DURATION));
     }
 
-    pub fn start_remove_with_tx(tx: Sender<RemoveDeviceRes>, mac_address: MacAddress) {
-        task::spawn_blocking(move || tx.blocking_send(remove_device(mac_address)));
+    /// Starts a scan, replacing the running one, whose devices found so far are dropped with it
+    fn search(&mut self, options: DiscoverOptions) {
+        self.scan_found.clear();
+        self.scan_task = Some(self.backend.search_devices_async(options));
     }
 
+    /// Shows the devices found so far, and once the scan completes, drops the devices it didn't find.
     pub fn try_update_with_scan_result(&mut self) {
-        if let Some(mut rx) = self.scan_recv.take() {
-            if let Ok(devices) = rx.try_recv() {
-                match devices {
-                    Ok(mut devices) => {
-                        devices.sort_by_key(|bd| bd.mac_address);
-                        self.devices = devices;
-                    }
-                    Err(err) => tracing::error!("{err}"),
+        let Some(task) = &mut self.scan_task else {
+            return;
+        };
+
+        // checked before taking the found devices, so none are found after the last take
+        let res = task.try_take();
+        let found = task.found().collect::<Vec<_>>();
+
+        for device in found {
+            self.scan_found.push(device.clone());
+
+            let events = self.watcher.update_device(device);
+            self.handle_device_events(events);
+        }
+
+        if let Some(res) = res {
+            self.scan_task = None;
+
+            let found = std::mem::take(&mut self.scan_found);
+
+            match res {
+                Ok(()) => {
+                    let events = self.watcher.update(found);
+                    self.handle_device_events(events);
                 }
-                self.scan_recv = None;
-            } else {
-                self.scan_recv = Some(rx);
+                Err(err) => tracing::error!("{err}"),
             }
         }
     }
 
+    /// Looks up a single device again, e.g. after connecting to it, instead of rescanning every device.
+    pub fn start_refresh(&mut self, mac_address: MacAddress) {
+        debug!("Refreshing {mac_address}");
+
+        self.refresh_tasks.push((mac_address, self.backend.get_device_async(mac_address)));
+    }
+
+    /// Updates the cards of the refreshed devices.
+    pub fn try_update_with_refresh_results(&mut self) {
+        for (mac_address, res) in take_finished(&mut self.refresh_tasks) {
+            let events = match res {
+                Ok(device) => self.watcher.update_device(device),
+                // e.g. a removed device the OS no longer remembers
+                Err(err) if err.kind() == ErrorKind::InvalidMacAddress => self.watcher.remove_device(mac_address),
+                Err(err) => {
+                    tracing::error!("{err}");
+                    continue;
+                }
+            };
+
+            self.handle_device_events(events);
+        }
+    }
+
+    pub fn handle_device_events(&mut self, events: Vec<DeviceEvent>) {
+        for event in events {
+            let name = |bd: &BluetoothDevice| bd.display_name().map(|name| name.into_owned()).unwrap_or_else(|| bd.mac_address().to_string());
+
+            match event {
+                DeviceEvent::Connected { new, .. } => {
+                    info!("{} connected", name(&new));
+                    self.add_timeout(new.mac_address());
+                }
+                DeviceEvent::Appeared { new } if new.is_connected() => {
+                    self.add_timeout(new.mac_address());
+                }
+                DeviceEvent::Disconnected { new, .. } => {
+                    info!("{} disconnected", name(&new));
+                    self.timeouts.retain(|timeout| timeout.mac_address != new.mac_address());
+                    self.scheduler.cancel(new.mac_address());
+                }
+                DeviceEvent::Vanished { old } => {
+                    self.timeouts.retain(|timeout| timeout.mac_address != old.mac_address());
+                    self.scheduler.cancel(old.mac_address());
+                }
+                DeviceEvent::Forgotten { new, .. } => debug!("{} was forgotten", name(&new)),
+                DeviceEvent::Appeared { .. } | DeviceEvent::Renamed { .. } => {}
+            }
+        }
+    }
+
+    /// Starts the timeout of a device which connected, with its remembered timeout if it has one.
+    ///
+    /// A device which already has a timeout keeps it, so it isn't started over.
+    pub fn add_timeout(&mut self, mac_address: MacAddress) {
+        if self.timeouts.iter().any(|timeout| timeout.mac_address == mac_address) {
+            return;
+        }
+
+        let timeout = Timeout::for_device(mac_address, &self.profiles);
+
+        timeout.start(&self.scheduler);
+        self.timeouts.push(timeout);
+    }
+
     pub fn check_remove_connect_res(&mut self) {
-        let mut updated = false;
+        let mut changed = Vec::new();
+
+        for (mac_address, res) in take_finished(&mut self.disconnect_tasks) {
+            changed.push(mac_address);
+
+            if let Err(err) = res {
+                tracing::error!("{err}");
+            }
+        }
 
-        while let Ok(res) = self.remove_res_channel.1.try_recv() {
-            updated = true;
+        for (mac_address, res) in take_finished(&mut self.remove_tasks) {
+            changed.push(mac_address);
 
             if let Err(err) = res {
                 tracing::error!("{err}");
             }
         }
 
-        while let Ok(res) = self.connect_res_channel.1.try_recv() {
-            updated = true;
+        for (mac_address, res) in take_finished(&mut self.connect_tasks) {
+            changed.push(mac_address);
+
+            // a displayed passkey has been typed by now, or pairing failed
+            self.pairing.end_display();
 
             if let Err(err) = res {
                 tracing::error!("{err}");
             }
         }
 
-        if updated {
-            self.start_scan();
+        // even a failed operation may have changed the device, e.g. paired it but not connected
+        for mac_address in changed {
+            self.start_refresh(mac_address);
         }
     }
 
+    /// Tracks the operations the scheduler started for devices which timed out, and the warnings for ones about to.
     pub fn process_timeout(&mut self) {
-        self.timeouts.retain_mut(|timeout| {
-            if let Some(ref mut duration) = timeout.duration {
-                match duration.checked_sub(self.last_timeout_update.elapsed()) {
-                    Some(res) => *duration = res,
-                    None => {
-                        *duration = Duration::ZERO;
+        while let Ok((mac_address, action, task)) = self.expired_rx.try_recv() {
+            debug!("{mac_address} timed out");
 
-                        let tx = self.remove_res_channel.0.clone();
-                        let mac_address = timeout.mac_address;
+            match action {
+                ExpiryAction::Disconnect => self.disconnect_tasks.push((mac_address, task)),
+                ExpiryAction::Remove => self.remove_tasks.push((mac_address, task)),
+            }
+        }
 
-                        Self::start_remove_with_tx(tx, mac_address);
-                    }
-                }
+        while let Ok(mac_address) = self.warning_rx.try_recv() {
+            if !self.warnings.contains(&mac_address) {
+                self.warnings.push(mac_address);
             }
+        }
 
-            self.devices.iter().any(|bd| bd.connected && bd.mac_address == timeout.mac_address)
+        // a warning is over once its timeout expires, or moves further away, e.g. when an idle device is used again
+        self.warnings.retain(|&mac_address| {
+            let warn_before = self.timeouts.iter()
+                .find(|timeout| timeout.mac_address == mac_address)
+                .and_then(|timeout| timeout.warn_before);
+
+            match (self.scheduler.remaining(mac_address), warn_before) {
+                (Some(remaining), Some(warn_before)) => !remaining.is_zero() && remaining <= warn_before,
+                _ => false,
+            }
         });
+    }
 
-        for bd in &self.devices {
-            if bd.connected && !self.timeouts.iter().any(|timeout| bd.mac_address == timeout.mac_address) {
-                self.timeouts.push(Timeout::default_from(bd.mac_address))
+    /// Extends the device's timeout, or applies its expiry action right away, as picked on its warning.
+    pub fn answer_warning(&mut self, mac_address: MacAddress, choice: WarningChoice) {
+        self.warnings.retain(|&warned| warned != mac_address);
+
+        let Some(timeout) = self.timeouts.iter_mut().find(|timeout| timeout.mac_address == mac_address) else {
+            return;
+        };
+
+        match choice {
+            WarningChoice::Extend(by) => timeout.extend(&self.scheduler, by),
+            WarningChoice::ActNow => {
+                self.scheduler.cancel(mac_address);
+
+                match timeout.expiry_action {
+                    ExpiryAction::Disconnect => self.disconnect_tasks.push((mac_address, self.backend.disconnect_device_async(mac_address))),
+                    ExpiryAction::Remove => self.remove_tasks.push((mac_address, self.backend.remove_device_async(mac_address))),
+                }
             }
         }
+    }
 
-        self.last_timeout_update = Instant::now();
+    /// The device's name, or its address if it doesn't have one
+    pub fn device_name(&self, mac_address: MacAddress) -> String {
+        self.watcher.device(mac_address)
+            .and_then(|bd| bd.display_name().map(|name| name.into_owned()))
+            .unwrap_or_else(|| mac_address.to_string())
     }
     
     pub fn process_logs(&mut self) {
@@ -148,15 +335,23 @@ impl eframe::App for BluetoothApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.try_update_with_scan_result();
         self.check_remove_connect_res();
+        self.try_update_with_refresh_results();
         self.process_timeout();
         self.process_logs();
 
-        ctx.request_repaint_after_secs(2.0);
+        // a warning counts down by the second
+        ctx.request_repaint_after_secs(if self.warnings.is_empty() { 2.0 } else { 1.0 });
+
+        self.pairing.poll();
+
+        if let Some(mac_address) = self.pairing.mac_address() {
+            let name = self.device_name(mac_address);
+
+            self.pairing.show(ctx, &name);
+        }
 
         if let Some(editing) = self.editing.as_mut() {
-            let opt_device = self.devices
-                .iter()
-                .find(|d| d.mac_address == editing.mac_address);
+            let opt_device = self.watcher.device(editing.mac_address);
 
             let opt_timeout = self.timeouts
                 .iter_mut()
@@ -177,7 +372,7 @@ impl eframe::App for BluetoothApp {
                     .collapsible(false)
                     .open(&mut keep_open)
                     .fixed_pos([pos.x, pos.y])
-                    .show(ctx, |ui| ui.add(TimeoutEditUi { device, timeout, editing }));
+                    .show(ctx, |ui| ui.add(TimeoutEditUi { device, timeout, scheduler: &self.scheduler, profiles: &mut self.profiles, editing }));
 
                 if !keep_open {
                     self.editing = None;
@@ -187,26 +382,53 @@ impl eframe::App for BluetoothApp {
             }
         }
 
-        if !self.logs.is_empty() {
+        if !self.logs.is_empty() || !self.warnings.is_empty() {
+            let mut choices = Vec::new();
+
             egui::Area::new("log_area".into())
                 .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
                 .show(ctx, |ui| {
                     ui.set_max_width(225.0);
+
+                    for &mac_address in &self.warnings {
+                        let (Some(timeout), Some(remaining)) = (
+                            self.timeouts.iter().find(|timeout| timeout.mac_address == mac_address),
+                            self.scheduler.remaining(mac_address),
+                        ) else {
+                            continue;
+                        };
+
+                        let warning = WarningNotification {
+                            name: &self.device_name(mac_address),
+                            remaining,
+                            action: timeout.expiry_action,
+                        };
+
+                        if let Some(choice) = warning.show(ui) {
+                            choices.push((mac_address, choice));
+                        }
+
+                        ui.add_space(4.0);
+                    }
                     
                     for notif in self.logs.iter().rev().take(3) {
                         ui.add(Notification(notif));
                         ui.add_space(4.0);
                     }
                 });
+
+            for (mac_address, choice) in choices {
+                self.answer_warning(mac_address, choice);
+            }
         }
         
         egui::CentralPanel::default().show(ctx, |ui| {
             let card_margin = Margin::same(2);
 
-            let any_connected = self.devices.iter().any(|bd| bd.connected);
+            let any_connected = self.watcher.devices().any(|bd| bd.is_connected());
 
             ui.scope(|ui| {
-                if self.editing.is_some() {
+                if self.editing.is_some() || self.pairing.is_open() {
                     ui.disable();
                 }
 
@@ -226,24 +448,30 @@ impl eframe::App for BluetoothApp {
                         .max_height(200.0)
                         .auto_shrink([false, true])
                         .show(ui, |ui| {
-                            for device in self.devices.iter().filter(|&bd| bd.connected).cloned() {
-                                let timeout = if let Some(timeout) = self.timeouts
-                                    .iter_mut()
-                                    .find(|t| t.mac_address == device.mac_address)
-                                {
-                                    timeout
-                                } else {
-                                    self.timeouts.push(Timeout::default_from(device.mac_address));
-
-                                    self.timeouts.last_mut()
-                                        .expect("should exist")
-                                };
+                            // devices already connected when they were first seen have no timeout yet
+                            let connected = self.watcher.devices()
+                                .filter(|bd| bd.is_connected())
+                                .map(|bd| bd.mac_address())
+                                .collect::<Vec<_>>();
+
+                            for mac_address in connected {
+                                self.add_timeout(mac_address);
+                            }
+
+                            for device in self.watcher.devices().filter(|bd| bd.is_connected()) {
+                                let timeout = self.timeouts
+                                    .iter()
+                                    .find(|t| t.mac_address == device.mac_address())
+                                    .expect("connected devices should have a timeout");
 
                                 ui.add(ConnectedDeviceCard {
                                     outer_margin: card_margin,
-                                    remove_tx: self.remove_res_channel.0.clone(),
-                                    device: &device,
+                                    backend: self.backend.clone(),
+                                    disconnect_tasks: &mut self.disconnect_tasks,
+                                    remove_tasks: &mut self.remove_tasks,
+                                    device,
                                     timeout,
+                                    remaining: self.scheduler.remaining(device.mac_address()),
                                     editing: &mut self.editing,
                                 });
                             }
@@ -252,7 +480,7 @@ impl eframe::App for BluetoothApp {
                     ui.separator();
                 }
 
-                if self.devices.iter().any(|bd| !bd.connected) {
+                if self.watcher.devices().any(|bd| !bd.is_connected()) {
                     ui.horizontal(|ui| {
                         ui.with_layout(Layout::left_to_right(Align::Center), |ui|
                             ui.heading("Available")
@@ -269,12 +497,13 @@ impl eframe::App for BluetoothApp {
                         .id_salt("available_scroll_area")
                         .auto_shrink([false, false])
                         .show(ui, |ui| {
-                            for device in self.devices.iter()
-                                .filter(|bd| !bd.connected)
+                            for device in self.watcher.devices()
+                                .filter(|bd| !bd.is_connected())
 
                             {
                                 ui.add(AvailableDeviceCard {
-                                    connect_tx: self.connect_res_channel.0.clone(),
+                                    backend: self.backend.clone(),
+                                    connect_tasks: &mut self.connect_tasks,
                                     outer_margin: card_margin,
                                     device,
                                 });
@@ -282,7 +511,7 @@ impl eframe::App for BluetoothApp {
                         });
                 }
 
-                if self.devices.is_empty() {
+                if self.watcher.is_empty() {
                     Frame::new()
                         .corner_radius(8.0)
                         .stroke(Stroke::new(2.0, Color32::DARK_GRAY))
@@ -298,20 +527,122 @@ impl eframe::App for BluetoothApp {
     }
 
     fn on_exit(&mut self, _: Option<&Context>) {
-        let mut handles = Vec::new();
+        self.scheduler.stop();
 
-        for timeout in self.timeouts.drain(..) {
-            if timeout.remove_on_close {
-                handles.push(
-                    task::spawn_blocking(move || remove_device(timeout.mac_address))
-                );
-            }
+        // every task starts right away, and is awaited on the runtime once the window is gone
+        for timeout in self.timeouts.drain(..).filter(|timeout| timeout.act_on_close) {
+            let task = match timeout.expiry_action {
+                ExpiryAction::Disconnect => self.backend.disconnect_device_async(timeout.mac_address),
+                ExpiryAction::Remove => self.backend.remove_device_async(timeout.mac_address),
+            };
+
+            let _ = self.exit_tx.send(task);
         }
+    }
+}
 
-        for handle in handles {
-            if let Ok(Err(err)) = handle.into_future().block_on() {
-                tracing::error!("{err}");
-            }
+/// Removes the tasks which have finished, returning their results along with the device each was for
+fn take_finished<T>(tasks: &mut Vec<(MacAddress, BackendTask<T>)>) -> Vec<(MacAddress, T)> {
+    let mut finished = Vec::new();
+
+    tasks.retain_mut(|(mac_address, task)| match task.try_take() {
+        Some(res) => {
+            finished.push((*mac_address, res));
+            false
+        }
+        None => true,
+    });
+
+    finished
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use eframe::egui;
+    use tokio::sync::mpsc;
+    use windows_bluetooth::{MockBackend, MockDevice};
+    use crate::pairing::PairingDialog;
+    use crate::profiles::{Profiles, TimeoutProfile};
+    use crate::scheduler::{ManualClock, TimeoutScheduler};
+    use crate::test_devices::HEADPHONES;
+    use crate::timeout::ExpiryAction;
+    use super::BluetoothApp;
+
+    /// Runs the app's per-frame processing until `done`, as its update would while repainting
+    async fn run_until(app: &mut BluetoothApp, mut done: impl FnMut(&BluetoothApp) -> bool) {
+        let start = Instant::now();
+
+        while !done(app) {
+            assert!(start.elapsed() < Duration::from_secs(5), "the app didn't get there in time");
+
+            app.try_update_with_scan_result();
+            app.check_remove_connect_res();
+            app.try_update_with_refresh_results();
+            app.process_timeout();
+
+            tokio::task::yield_now().await;
+            thread::sleep(Duration::from_millis(1));
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn connected_devices_time_out() {
+        let backend = Arc::new(MockBackend::with_devices([
+            MockDevice::new(HEADPHONES).name("WH-1000XM4").remembered(true).authenticated(true).connected(true),
+        ]));
+
+        let mut profiles = Profiles::default();
+        profiles.remember(HEADPHONES, TimeoutProfile {
+            timeout: Some("10m".to_string()),
+            idle: false,
+            warn_before: None,
+            expiry_action: ExpiryAction::Disconnect,
+            act_on_close: false,
+        }).unwrap();
+
+        let clock = Arc::new(ManualClock::new());
+        let ctx = egui::Context::default();
+        let (pairing, _agent) = PairingDialog::new(ctx.clone());
+        let (_log_tx, log_rx) = mpsc::channel(5);
+        let (exit_tx, _exit_rx) = mpsc::unbounded_channel();
+
+        let mut app = BluetoothApp::new_now_with_log_rx(
+            backend.clone(),
+            log_rx,
+            pairing,
+            TimeoutScheduler::new(clock.clone()),
+            profiles,
+            exit_tx,
+            ctx,
+        );
+
+        app.start_scan();
+        run_until(&mut app, |app| app.scan_task.is_none()).await;
+
+        assert_eq!(app.timeouts.iter().map(|timeout| timeout.mac_address).collect::<Vec<_>>(), [HEADPHONES]);
+        assert_eq!(app.scheduler.remaining(HEADPHONES), Some(Duration::from_secs(10 * 60)));
+
+        clock.advance(Duration::from_secs(9 * 60));
+        app.scheduler.wake();
+        thread::sleep(Duration::from_millis(20));
+        app.process_timeout();
+
+        assert!(app.disconnect_tasks.is_empty() && app.remove_tasks.is_empty());
+        assert_eq!(app.scheduler.remaining(HEADPHONES), Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(60));
+        app.scheduler.wake();
+
+        // disconnected by the mock, and dropped from the app once it refreshed the device
+        run_until(&mut app, |app| app.timeouts.is_empty()).await;
+
+        let headphones = backend.device(HEADPHONES).unwrap();
+        assert!(!headphones.is_connected() && headphones.is_remembered());
+        assert!(!app.watcher.device(HEADPHONES).unwrap().is_connected());
+
+        app.scheduler.stop();
+    }
+}
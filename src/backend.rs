@@ -0,0 +1,194 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{Local, TimeDelta};
+use windows_bluetooth::{ActivitySource, Fault, FaultInjectingBackend, FaultRates, mac, MockActivity, MockBackend, MockDevice, MockPairing, Operation, Profile, RecordingBackend, ReplayBackend};
+use crate::app::SharedBackend;
+
+/// Environment variable used to pick a backend other than the platform's, either `mock` or `replay:<recording path>`
+pub const BACKEND_ENV_VAR: &str = "BLUETOOTH_TIMEOUT_BACKEND";
+/// Environment variable with the address of the radio to use, for machines with more than one
+pub const RADIO_ENV_VAR: &str = "BLUETOOTH_TIMEOUT_RADIO";
+/// Environment variable with an RNG seed, which makes backend calls randomly fail or stall for exercising error handling
+pub const FAULTS_ENV_VAR: &str = "BLUETOOTH_TIMEOUT_FAULTS";
+/// Environment variable with a path to record every backend call to, for reproducing bug reports
+pub const RECORD_ENV_VAR: &str = "BLUETOOTH_TIMEOUT_RECORD";
+
+pub fn from_env() -> SharedBackend {
+    let backend: SharedBackend = match env::var(BACKEND_ENV_VAR).ok().as_deref() {
+        Some("mock") => Arc::new(demo_backend()),
+        Some(name) if name.starts_with("replay:") => {
+            let path = &name["replay:".len()..];
+
+            let replay = ReplayBackend::open(path)
+                .unwrap_or_else(|err| panic!("failed to open recording at {path}: {err}"));
+
+            Arc::new(replay.with_recorded_latency())
+        }
+        Some(other) => panic!("unknown backend `{other}` set in {BACKEND_ENV_VAR}"),
+        None => match env::var(RADIO_ENV_VAR) {
+            Ok(address) => radio_backend(&address),
+            Err(_) => windows_bluetooth::platform_backend()
+                .unwrap_or_else(|| panic!("no bluetooth backend is available on this platform, set {BACKEND_ENV_VAR}=mock for a simulated one")),
+        },
+    };
+
+    let backend: SharedBackend = match env::var(FAULTS_ENV_VAR) {
+        Ok(seed) => {
+            let seed = seed.parse()
+                .unwrap_or_else(|err| panic!("{FAULTS_ENV_VAR} should be a number: {err}"));
+
+            Arc::new(FaultInjectingBackend::with_rng(backend, seed, fault_rates()))
+        }
+        Err(_) => backend,
+    };
+
+    // record after injecting faults, so a recording reproduces them as well
+    match env::var_os(RECORD_ENV_VAR) {
+        Some(path) => Arc::new(
+            RecordingBackend::create(backend, &path)
+                .unwrap_or_else(|err| panic!("failed to create recording at {}: {err}", path.display()))
+        ),
+        None => backend,
+    }
+}
+
+/// How the app tells when devices are in use for idle timeouts, if it can with the backend picked by [`from_env`]
+pub fn activity_from_env() -> Option<Arc<dyn ActivitySource>> {
+    match env::var(BACKEND_ENV_VAR).ok().as_deref() {
+        Some("mock") => Some(Arc::new(demo_activity())),
+        // a replayed recording's devices aren't around to be used
+        Some(_) => None,
+        None => platform_activity(),
+    }
+}
+
+#[cfg(windows)]
+fn platform_activity() -> Option<Arc<dyn ActivitySource>> {
+    Some(Arc::new(windows_bluetooth::AudioSessionActivity::new(Duration::from_secs(1))))
+}
+
+#[cfg(not(windows))]
+fn platform_activity() -> Option<Arc<dyn ActivitySource>> {
+    None
+}
+
+#[cfg(windows)]
+fn radio_backend(address: &str) -> SharedBackend {
+    use windows_bluetooth::Win32Backend;
+
+    // e.g. an unplugged dongle, where any radio is better than the app crashing without a window to say why
+    match find_radio(address) {
+        Ok(radio) => Arc::new(Win32Backend::with_radio(radio)),
+        Err(err) => {
+            tracing::error!("{err}, using any radio instead");
+            Arc::new(Win32Backend::new())
+        }
+    }
+}
+
+/// The radio with the address set in [`RADIO_ENV_VAR`], or why it can't be used along with the radios which can
+#[cfg(windows)]
+fn find_radio(address: &str) -> Result<windows_bluetooth::Radio, String> {
+    use windows_bluetooth::{discover_radios, MacAddress};
+
+    let radios = discover_radios()
+        .map_err(|err| format!("Failed to list bluetooth radios for {RADIO_ENV_VAR}: {err}"))?;
+
+    let available = match radios.is_empty() {
+        true => "none".to_owned(),
+        false => radios.iter()
+            .map(|radio| format!("{} ({})", radio.address, radio.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    let address = address.parse::<MacAddress>()
+        .map_err(|err| format!("{RADIO_ENV_VAR} should be a radio's address, available radios: {available}: {err}"))?;
+
+    radios.into_iter()
+        .find(|radio| radio.address == address)
+        .ok_or_else(|| format!("No radio with address {address} set in {RADIO_ENV_VAR}, available radios: {available}"))
+}
+
+#[cfg(not(windows))]
+fn radio_backend(_: &str) -> SharedBackend {
+    panic!("{RADIO_ENV_VAR} is only supported on Windows")
+}
+
+/// Simulated backend with a handful of devices, for demoing the app on machines without a radio
+fn demo_backend() -> MockBackend {
+    let yesterday = Local::now().naive_local() - TimeDelta::days(1);
+
+    let backend = MockBackend::with_devices([
+        MockDevice::new(mac!("00:1A:7D:DA:71:13"))
+            .name("WH-1000XM4")
+            .class(0x240404)
+            .service(Profile::A2dpSink)
+            .service(Profile::AvrcpTarget)
+            .service(Profile::Handsfree)
+            .connected(true)
+            .remembered(true)
+            .authenticated(true)
+            .last_used(yesterday),
+        MockDevice::new(mac!("34:88:5D:10:2B:C4"))
+            .name("MX Master 3")
+            .class(0x002580)
+            .service(Profile::Hid)
+            .remembered(true)
+            .authenticated(true)
+            .last_used(yesterday),
+        MockDevice::new(mac!("F4:4E:FD:63:0A:91"))
+            .name("Living Room Speaker")
+            .class(0x240414)
+            .service(Profile::A2dpSink)
+            .service(Profile::AvrcpTarget),
+        MockDevice::new(mac!("00:01:4A:3E:91:C2"))
+            .class(0x240418),
+        MockDevice::new(mac!("7A:21:9C:44:E0:05")),
+    ]);
+
+    // in pairing mode, so only a deep scan finds it
+    backend.add_nearby_device(
+        MockDevice::new(mac!("2C:41:A1:07:5E:38"))
+            .name("Bose QuietComfort 45")
+            .class(0x240418)
+            .service(Profile::A2dpSink)
+            .service(Profile::AvrcpTarget)
+            .service(Profile::Handsfree)
+    );
+
+    // exercises the pairing dialog, since neither is paired yet
+    backend.set_pairing(mac!("F4:4E:FD:63:0A:91"), MockPairing::Pin("0000".to_owned()));
+    backend.set_pairing(mac!("2C:41:A1:07:5E:38"), MockPairing::ConfirmPasskey(482_913));
+
+    backend.set_latency(Operation::Discover, Duration::from_millis(800));
+    backend.set_latency(Operation::Get, Duration::from_millis(100));
+    backend.set_latency(Operation::Connect, Duration::from_secs(2));
+    backend.set_latency(Operation::Disconnect, Duration::from_millis(300));
+    backend.set_latency(Operation::Remove, Duration::from_millis(500));
+
+    backend
+}
+
+/// The demo headphones are playing music, so an idle timeout on them doesn't run out until they're disconnected
+fn demo_activity() -> MockActivity {
+    let activity = MockActivity::new();
+
+    activity.start_playing(mac!("00:1A:7D:DA:71:13"));
+
+    activity
+}
+
+fn fault_rates() -> FaultRates {
+    FaultRates::new()
+        .with(Operation::Discover, Fault::BluetoothUnavailable, 0.2)
+        .with(Operation::Discover, Fault::Slow(Duration::from_secs(3)), 0.2)
+        .with(Operation::Get, Fault::BluetoothUnavailable, 0.1)
+        .with(Operation::Connect, Fault::Cancelled, 0.3)
+        .with(Operation::Connect, Fault::Slow(Duration::from_secs(5)), 0.2)
+        .with(Operation::Disconnect, Fault::BluetoothUnavailable, 0.1)
+        .with(Operation::Remove, Fault::NotRemembered, 0.2)
+        .with(Operation::Remove, Fault::BluetoothUnavailable, 0.1)
+        .with(Operation::Remove, Fault::Slow(Duration::from_secs(3)), 0.2)
+}
@@ -1,17 +1,21 @@
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 use eframe::egui::{Align, Color32, FontId, Frame, Layout, Margin, Response, Ui, Widget};
 use eframe::egui::text::LayoutJob;
-use tokio::sync::mpsc::Sender;
-use windows_bluetooth::BluetoothDevice;
-use crate::app::{BluetoothApp, ConnectToDeviceRes, RemoveDeviceRes};
+use windows_bluetooth::{AsyncBluetoothBackend, BackendTask, BluetoothDevice, MacAddress};
+use crate::app::{ConnectToDeviceRes, DisconnectDeviceRes, RemoveDeviceRes, SharedBackend};
 use crate::editing::TimeoutEditing;
 use crate::timeout::Timeout;
 
 pub struct ConnectedDeviceCard<'a> {
     pub outer_margin: Margin,
-    pub remove_tx: Sender<RemoveDeviceRes>,
+    pub backend: SharedBackend,
+    pub disconnect_tasks: &'a mut Vec<(MacAddress, BackendTask<DisconnectDeviceRes>)>,
+    pub remove_tasks: &'a mut Vec<(MacAddress, BackendTask<RemoveDeviceRes>)>,
     pub device: &'a BluetoothDevice,
     pub timeout: &'a Timeout,
+    /// Time left until the device times out
+    pub remaining: Option<Duration>,
     pub editing: &'a mut Option<TimeoutEditing>,
 }
 
@@ -24,22 +28,26 @@ impl Widget for ConnectedDeviceCard<'_> {
             .show(ui, |ui| {
                 ui.vertical(|ui| {
                     ui.horizontal(|ui| {
-                        let name = self.device.name.as_deref().unwrap_or("Unknown");
+                        let name = self.device.display_name();
 
                         ui.with_layout(Layout::left_to_right(Align::Center), |ui|
-                            ui.label(TruncatedName(name, 20).to_string())
+                            ui.label(TruncatedName(name.as_deref().unwrap_or("Unknown"), 20).to_string())
                         );
 
-                        ui.with_layout(Layout::right_to_left(Align::Center), |ui|
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                             if ui.button("Remove").clicked() {
-                                BluetoothApp::start_remove_with_tx(self.remove_tx, self.device.mac_address);
+                                self.remove_tasks.push((self.device.mac_address(), self.backend.remove_device_async(self.device.mac_address())));
                             }
-                        )
+
+                            if ui.button("Disconnect").clicked() {
+                                self.disconnect_tasks.push((self.device.mac_address(), self.backend.disconnect_device_async(self.device.mac_address())));
+                            }
+                        })
                     });
 
                     ui.horizontal(|ui| {
                         let left = ui.with_layout(Layout::left_to_right(Align::Center),|ui|
-                            ui.label(format!("Timeout: {}", self.timeout.duration_str_or_none()))
+                            ui.label(format!("Timeout: {}", self.timeout.remaining_str(self.remaining)))
                         );
                         
                         let right = ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
@@ -55,12 +63,15 @@ impl Widget for ConnectedDeviceCard<'_> {
             .response
             .on_hover_text(LayoutJob::simple(
                 format!(
-                    "MAC Address: {}\nConnected: {}\nRemembered: {}\nLast Used: {:?}\nLast Seen: {:?}",
-                    self.device.mac_address,
-                    self.device.connected,
-                    self.device.remembered,
-                    self.device.last_used,
-                    self.device.last_seen,
+                    "MAC Address: {}\nVendor: {}\nClass: {}\nProfiles: {}\nConnected: {}\nRemembered: {}\nLast Used: {:?}\nLast Seen: {:?}",
+                    self.device.mac_address(),
+                    vendor_str(self.device),
+                    self.device.class(),
+                    ProfileList(self.device),
+                    self.device.is_connected(),
+                    self.device.is_remembered(),
+                    self.device.last_used(),
+                    self.device.last_seen(),
                 ),
                 FontId::proportional(11.0),
                 ui.style().visuals.text_color(),
@@ -70,8 +81,9 @@ impl Widget for ConnectedDeviceCard<'_> {
 }
 
 pub struct AvailableDeviceCard<'a> {
+    pub backend: SharedBackend,
     pub outer_margin: Margin,
-    pub connect_tx: Sender<ConnectToDeviceRes>,
+    pub connect_tasks: &'a mut Vec<(MacAddress, BackendTask<ConnectToDeviceRes>)>,
     pub device: &'a BluetoothDevice,
 }
 
@@ -83,27 +95,37 @@ impl Widget for AvailableDeviceCard<'_> {
             .inner_margin(Margin::same(5))
             .show(ui, |ui| {
                 ui.horizontal(|ui| {
-                    let name = self.device.name.as_deref().unwrap_or("Unknown");
+                    let name = self.device.display_name();
 
                     ui.with_layout(Layout::left_to_right(Align::Center), |ui|
-                        ui.label(TruncatedName(name, 20).to_string())
+                        ui.label(TruncatedName(name.as_deref().unwrap_or("Unknown"), 20).to_string())
                     );
 
-                    ui.with_layout(Layout::right_to_left(Align::Center), |ui|
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                         if ui.button("Connect").clicked() {
-                            BluetoothApp::start_connect_with_tx(self.connect_tx, self.device.mac_address);
+                            self.connect_tasks.push((self.device.mac_address(), self.backend.connect_to_device_async(self.device.mac_address())));
                         }
-                    )
+
+                        // for a pairing which broke, e.g. after the device was reset, which needs the device in pairing mode
+                        if self.device.is_remembered()
+                            && ui.button("Re-pair").on_hover_text("Forget the pairing and pair again").clicked()
+                        {
+                            self.connect_tasks.push((self.device.mac_address(), self.backend.pair_device_async(self.device.mac_address())));
+                        }
+                    })
                 })
             })
             .response
             .on_hover_text(LayoutJob::simple(
                 format!(
-                    "MAC Address: {}\nRemembered: {}\nLast Used: {:?}\nLast Seen: {:?}",
-                    self.device.mac_address,
-                    self.device.remembered,
-                    self.device.last_used,
-                    self.device.last_seen,
+                    "MAC Address: {}\nVendor: {}\nClass: {}\nProfiles: {}\nRemembered: {}\nLast Used: {:?}\nLast Seen: {:?}",
+                    self.device.mac_address(),
+                    vendor_str(self.device),
+                    self.device.class(),
+                    ProfileList(self.device),
+                    self.device.is_remembered(),
+                    self.device.last_used(),
+                    self.device.last_seen(),
                 ),
                 FontId::proportional(11.0),
                 ui.style().visuals.text_color(),
@@ -112,6 +134,14 @@ impl Widget for AvailableDeviceCard<'_> {
     }
 }
 
+fn vendor_str(device: &BluetoothDevice) -> &'static str {
+    match device.mac_address().vendor() {
+        Some(vendor) => vendor,
+        None if device.mac_address().is_locally_administered() => "None (randomized address)",
+        None => "Unknown",
+    }
+}
+
 struct TruncatedName<'a>(&'a str, usize);
 
 impl Display for TruncatedName<'_> {
@@ -122,4 +152,24 @@ impl Display for TruncatedName<'_> {
             write!(f, "{}", self.0)
         }
     }
+}
+
+/// The device's known profiles by their abbreviation, like "A2DP, AVRCP"
+struct ProfileList<'a>(&'a BluetoothDevice);
+
+impl Display for ProfileList<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut names = self.0.profiles()
+            .map(|profile| profile.short_name())
+            .collect::<Vec<_>>();
+
+        names.sort_unstable();
+        names.dedup();
+
+        if names.is_empty() {
+            write!(f, "Unknown")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
 }
\ No newline at end of file
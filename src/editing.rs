@@ -1,7 +1,9 @@
-use eframe::egui::{Response, Ui, Widget};
-use humantime::parse_duration;
+use eframe::egui::{Checkbox, ComboBox, Response, Ui, Widget};
+use chrono::Local;
 use windows_bluetooth::{BluetoothDevice, MacAddress};
-use crate::timeout::Timeout;
+use crate::profiles::Profiles;
+use crate::scheduler::TimeoutScheduler;
+use crate::timeout::{self, Expiry, ExpiryAction, Timeout, WARNING_LEADS};
 
 #[derive(Debug)]
 pub struct TimeoutEditing {
@@ -23,26 +25,76 @@ impl TimeoutEditing {
 pub struct TimeoutEditUi<'a> {
     pub device: &'a BluetoothDevice,
     pub timeout: &'a mut Timeout,
+    pub scheduler: &'a TimeoutScheduler,
+    pub profiles: &'a mut Profiles,
     pub editing: &'a mut TimeoutEditing,
 }
 
 impl Widget for TimeoutEditUi<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
         ui.vertical(|ui| {
-            let name = self.device.name.as_deref().unwrap_or("Unknown");
+            let name = self.device.display_name();
+            let name = name.as_deref().unwrap_or("Unknown");
 
             ui.label(format!("Editing {name}"));
 
-            ui.checkbox(&mut self.timeout.remove_on_close, "Remove on close")
-                .on_hover_text("Remove this device when the application closes");
+            let mac_address = self.timeout.mac_address;
+            let remaining = self.scheduler.remaining(mac_address);
+            let before = self.timeout.profile();
+
+            ui.horizontal(|ui| {
+                ui.label("On expiry: ");
+
+                ComboBox::from_id_salt("expiry_action")
+                    .selected_text(self.timeout.expiry_action.to_string())
+                    .show_ui(ui, |ui| {
+                        for action in ExpiryAction::ALL {
+                            if ui.selectable_value(&mut self.timeout.expiry_action, action, action.to_string()).changed() {
+                                self.scheduler.set_action(mac_address, action);
+                            }
+                        }
+                    });
+            })
+                .response
+                .on_hover_text("Disconnecting keeps the device paired, removing unpairs it");
+
+            ui.checkbox(&mut self.timeout.act_on_close, format!("{} on close", self.timeout.expiry_action))
+                .on_hover_text("Also do this when the application closes");
 
             ui.separator();
 
+            let idle = ui.add_enabled(self.scheduler.tracks_activity(), Checkbox::new(&mut self.timeout.idle, "Only while idle"))
+                .on_hover_text("Count down only while the device isn't playing audio, starting over whenever it does")
+                .on_disabled_hover_text("Telling when the device plays audio isn't supported here");
+
+            if idle.changed() && remaining.is_some() {
+                self.timeout.start(self.scheduler);
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Warn before: ");
+
+                let lead_str = |lead: Option<_>| lead.map(timeout::duration_str).unwrap_or("Never".into());
+
+                ComboBox::from_id_salt("warn_before")
+                    .selected_text(lead_str(self.timeout.warn_before))
+                    .show_ui(ui, |ui| {
+                        for lead in WARNING_LEADS {
+                            if ui.selectable_value(&mut self.timeout.warn_before, lead, lead_str(lead)).changed() {
+                                self.scheduler.set_warning(mac_address, lead);
+                            }
+                        }
+                    });
+            })
+                .response
+                .on_hover_text("Shows a warning with the option to extend the timeout before it runs out");
+
             ui.horizontal(|ui| {
-                ui.label(format!("Timeout: {}", self.timeout.duration_str_or_none()));
+                ui.label(format!("Timeout: {}", self.timeout.remaining_str(remaining)));
 
-                if self.timeout.duration.is_some() && ui.button("Remove").clicked() {
-                    self.timeout.duration = None;
+                if remaining.is_some() && ui.button("Remove").clicked() {
+                    self.scheduler.cancel(mac_address);
+                    self.timeout.expiry = None;
                 }
             });
 
@@ -51,19 +103,21 @@ impl Widget for TimeoutEditUi<'_> {
                 ui.label("New Timeout: ");
 
                 ui.text_edit_singleline(&mut self.editing.text_edit_buffer)
+                    .on_hover_text("A countdown like 1h 30m, or a time like 23:00 or tomorrow 08:00")
             })
                 .inner;
 
             let edit_has_focus = text_edit_resp.has_focus();
 
-            if self.editing.buffer_prev_had_focus && !edit_has_focus {
-                if let Ok(duration) = parse_duration(&self.editing.text_edit_buffer) {
-                    self.timeout.duration = Some(duration)
-                }
+            if self.editing.buffer_prev_had_focus && !edit_has_focus
+                && let Some(expiry) = Expiry::parse(&self.editing.text_edit_buffer, Local::now())
+            {
+                self.timeout.expiry = Some(expiry);
+                self.timeout.start(self.scheduler);
             }
 
             if !edit_has_focus {
-                self.editing.text_edit_buffer = self.timeout.duration_str().unwrap_or_default();
+                self.editing.text_edit_buffer = self.timeout.input_str(remaining);
 
                 self.editing.buffer_prev_had_focus = false;
             }
@@ -71,6 +125,24 @@ impl Widget for TimeoutEditUi<'_> {
             if text_edit_resp.clicked() {
                 self.editing.buffer_prev_had_focus = true;
             }
+
+            ui.separator();
+
+            let mut remembered = self.profiles.get(mac_address).is_some();
+
+            let remember = ui.checkbox(&mut remembered, "Remember for this device")
+                .on_hover_text("Start with this timeout whenever the device connects");
+
+            // a remembered timeout keeps up with the changes made to it
+            let saved = match remembered {
+                true if remember.changed() || self.timeout.profile() != before => self.profiles.remember(mac_address, self.timeout.profile()),
+                false if remember.changed() => self.profiles.forget(mac_address),
+                _ => Ok(()),
+            };
+
+            if let Err(err) = saved {
+                tracing::error!("Failed to save remembered timeouts: {err}");
+            }
         }).response
     }
 }
\ No newline at end of file
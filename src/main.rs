@@ -1,17 +1,27 @@
 #![windows_subsystem = "windows"]
 
 mod app;
+mod backend;
 mod editing;
 mod card;
 mod spinner;
 mod timeout;
 mod logging;
 mod notification;
+mod pairing;
+mod profiles;
+mod scheduler;
+#[cfg(test)]
+mod test_devices;
 
+use std::sync::Arc;
 use eframe::egui::ViewportBuilder;
 use eframe::icon_data;
 use tokio::sync::mpsc;
 use app::BluetoothApp;
+use pairing::PairingDialog;
+use profiles::Profiles;
+use scheduler::{SystemClock, TimeoutScheduler};
 
 #[tokio::main(flavor="current_thread")]
 async fn main() -> eframe::Result {
@@ -20,9 +30,21 @@ async fn main() -> eframe::Result {
     
     logging::init(tx).expect("init shouldn't fail");
     
+    let backend = backend::from_env();
+
+    let profiles = Profiles::from_env();
+
+    let scheduler = match backend::activity_from_env() {
+        Some(activity) => TimeoutScheduler::new(SystemClock).with_activity(activity),
+        None => TimeoutScheduler::new(SystemClock),
+    };
+
+    // operations started as the app closes, which are awaited once it has
+    let (exit_tx, mut exit_rx) = mpsc::unbounded_channel();
+
     let icon = icon_data::from_png_bytes(include_bytes!("../assets/icon.png")).expect("png bytes should be valid");
     
-    eframe::run_native(
+    let res = eframe::run_native(
         "Bluetooth Timeout",
         eframe::NativeOptions {
             viewport: ViewportBuilder::default()
@@ -35,7 +57,19 @@ async fn main() -> eframe::Result {
         Box::new(move |cc| {
             cc.egui_ctx.style_mut(|style| style.visuals.dark_mode = true);
 
-            Ok(Box::new(BluetoothApp::new_now_with_log_rx(rx)))
+            let (pairing, agent) = PairingDialog::new(cc.egui_ctx.clone());
+            backend.set_pairing_agent(Arc::new(agent));
+
+            Ok(Box::new(BluetoothApp::new_now_with_log_rx(backend, rx, pairing, scheduler, profiles, exit_tx, cc.egui_ctx.clone())))
         }),
-    )
+    );
+
+    // the app may not have been dropped yet, so its sender can't be waited on to close
+    while let Ok(task) = exit_rx.try_recv() {
+        if let Err(err) = task.await {
+            tracing::error!("{err}");
+        }
+    }
+
+    res
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
+use std::time::Duration;
 use eframe::egui::{Color32, Frame, Margin, Response, RichText, Stroke, Ui, Widget};
 use tracing::Level;
 use crate::logging::Log;
+use crate::timeout::{self, ExpiryAction};
 
 pub struct Notification<'a>(pub &'a Log);
 
@@ -23,4 +25,58 @@ impl Widget for Notification<'_> {
             }))
             .response
     }
-}
\ No newline at end of file
+}
+
+/// What the user picked on a [`WarningNotification`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WarningChoice {
+    Extend(Duration),
+    /// Apply the expiry action right away
+    ActNow,
+}
+
+/// Warns that a device is about to time out, with the option to extend its timeout.
+pub struct WarningNotification<'a> {
+    pub name: &'a str,
+    pub remaining: Duration,
+    pub action: ExpiryAction,
+}
+
+impl WarningNotification<'_> {
+    const EXTENSIONS: [(&'static str, Duration); 2] = [
+        ("+15 min", Duration::from_secs(15 * 60)),
+        ("+1 h", Duration::from_secs(60 * 60)),
+    ];
+
+    pub fn show(self, ui: &mut Ui) -> Option<WarningChoice> {
+        Frame::popup(ui.style())
+            .stroke(Stroke::new(1.0, Color32::ORANGE))
+            .inner_margin(Margin::same(8))
+            .show(ui, |ui| ui.vertical(|ui| {
+                ui.label(RichText::new("Timing out").color(Color32::ORANGE).strong());
+                let doing = match self.action {
+                    ExpiryAction::Disconnect => "Disconnecting",
+                    ExpiryAction::Remove => "Removing",
+                };
+
+                ui.label(RichText::new(format!("{doing} {} in {}", self.name, timeout::duration_str(self.remaining))).size(10.0));
+
+                ui.horizontal(|ui| {
+                    let mut choice = None;
+
+                    for (label, by) in Self::EXTENSIONS {
+                        if ui.small_button(label).clicked() {
+                            choice = Some(WarningChoice::Extend(by));
+                        }
+                    }
+
+                    if ui.small_button(format!("{} now", self.action)).clicked() {
+                        choice = Some(WarningChoice::ActNow);
+                    }
+
+                    choice
+                }).inner
+            }).inner)
+            .inner
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+use eframe::egui::{self, Key, RichText, Ui, Vec2, Window};
+use windows_bluetooth::{format_passkey, MacAddress, PairingAgent};
+
+/// How long the backend waits on the user to answer, about as long as devices stay in pairing mode
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A pairing request from the backend, waiting on the user
+#[derive(Debug)]
+pub enum PairingPrompt {
+    Pin { mac_address: MacAddress, reply: Sender<Option<String>> },
+    Passkey { mac_address: MacAddress, reply: Sender<Option<u32>> },
+    Confirm { mac_address: MacAddress, passkey: u32, reply: Sender<bool> },
+    Display { mac_address: MacAddress, passkey: u32 },
+}
+
+impl PairingPrompt {
+    pub fn mac_address(&self) -> MacAddress {
+        match self {
+            PairingPrompt::Pin { mac_address, .. }
+            | PairingPrompt::Passkey { mac_address, .. }
+            | PairingPrompt::Confirm { mac_address, .. }
+            | PairingPrompt::Display { mac_address, .. } => *mac_address,
+        }
+    }
+}
+
+/// Hands the backend's pairing requests to the [`PairingDialog`], blocking the backend until the user answers.
+#[derive(Debug)]
+pub struct DialogAgent {
+    prompts: Sender<PairingPrompt>,
+    ctx: egui::Context,
+}
+
+impl DialogAgent {
+    fn show(&self, prompt: PairingPrompt) {
+        let _ = self.prompts.send(prompt);
+
+        // the app only repaints every few seconds otherwise
+        self.ctx.request_repaint();
+    }
+
+    fn ask<T>(&self, prompt: impl FnOnce(Sender<T>) -> PairingPrompt) -> Option<T> {
+        self.ask_within(REPLY_TIMEOUT, prompt)
+    }
+
+    /// Rejects the request if it isn't answered within `timeout`, so the backend isn't blocked on a prompt no one sees.
+    fn ask_within<T>(&self, timeout: Duration, prompt: impl FnOnce(Sender<T>) -> PairingPrompt) -> Option<T> {
+        let (tx, rx) = mpsc::channel();
+
+        self.show(prompt(tx));
+
+        // the reply's sender is dropped without an answer if the app closes, which rejects the request
+        rx.recv_timeout(timeout).ok()
+    }
+}
+
+impl PairingAgent for DialogAgent {
+    fn request_pin(&self, mac_address: MacAddress) -> Option<String> {
+        self.ask(|reply| PairingPrompt::Pin { mac_address, reply }).flatten()
+    }
+
+    fn request_passkey(&self, mac_address: MacAddress) -> Option<u32> {
+        self.ask(|reply| PairingPrompt::Passkey { mac_address, reply }).flatten()
+    }
+
+    fn confirm_passkey(&self, mac_address: MacAddress, passkey: u32) -> bool {
+        self.ask(|reply| PairingPrompt::Confirm { mac_address, passkey, reply }).unwrap_or(false)
+    }
+
+    fn display_passkey(&self, mac_address: MacAddress, passkey: u32) {
+        self.show(PairingPrompt::Display { mac_address, passkey });
+    }
+}
+
+/// Shows the backend's pairing requests one at a time, in place of the OS's pairing prompts.
+#[derive(Debug)]
+pub struct PairingDialog {
+    prompts: Receiver<PairingPrompt>,
+    current: Option<PairingPrompt>,
+    input: String,
+}
+
+impl PairingDialog {
+    /// Returns the dialog along with the agent to give the backend, which repaints `ctx` on each request.
+    pub fn new(ctx: egui::Context) -> (Self, DialogAgent) {
+        let (tx, rx) = mpsc::channel();
+
+        let dialog = Self {
+            prompts: rx,
+            current: None,
+            input: String::new(),
+        };
+
+        (dialog, DialogAgent { prompts: tx, ctx })
+    }
+
+    /// Takes the next request, unless one is already shown
+    pub fn poll(&mut self) {
+        if self.current.is_none() {
+            self.current = self.prompts.try_recv().ok();
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn mac_address(&self) -> Option<MacAddress> {
+        self.current.as_ref().map(PairingPrompt::mac_address)
+    }
+
+    /// Closes a displayed passkey, which has nothing to answer, once pairing ends
+    pub fn end_display(&mut self) {
+        self.current.take_if(|prompt| matches!(prompt, PairingPrompt::Display { .. }));
+    }
+
+    /// Shows the current request for the device called `name`, if there is one
+    pub fn show(&mut self, ctx: &egui::Context, name: &str) {
+        if self.current.is_none() {
+            return;
+        }
+
+        let screen_size = ctx.screen_rect().size();
+        let popup_size = Vec2::new(200.0, 120.0);
+
+        let pos = (screen_size - popup_size) * 0.5;
+
+        let mut keep_open = true;
+
+        let answer = Window::new("Pair Device")
+            .resizable(false)
+            .movable(false)
+            .default_size(popup_size)
+            .collapsible(false)
+            .open(&mut keep_open)
+            .fixed_pos([pos.x, pos.y])
+            .show(ctx, |ui| self.prompt_ui(ui, name))
+            .and_then(|inner| inner.inner)
+            .flatten();
+
+        match answer {
+            Some(accept) => self.answer(accept),
+            // closing the window rejects the request
+            None if !keep_open => self.answer(false),
+            None => {}
+        }
+    }
+
+    /// Returns whether the user accepted or rejected the request, if they answered it
+    fn prompt_ui(&mut self, ui: &mut Ui, name: &str) -> Option<bool> {
+        let prompt = self.current.as_ref()?;
+
+        match prompt {
+            PairingPrompt::Pin { .. } => {
+                ui.label(format!("Enter the PIN of {name}"));
+                ui.label(RichText::new("This is usually 0000 or 1234 for headsets and speakers").small());
+
+                self.input_ui(ui, !self.input.is_empty() && self.input.len() <= 16)
+            }
+            PairingPrompt::Passkey { .. } => {
+                ui.label(format!("Enter the passkey {name} shows"));
+
+                self.input_ui(ui, parse_passkey(&self.input).is_some())
+            }
+            PairingPrompt::Confirm { passkey, .. } => {
+                ui.label(format!("Does {name} show this passkey?"));
+                ui.label(RichText::new(format_passkey(*passkey)).heading().monospace());
+
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        Some(true)
+                    } else if ui.button("No").clicked() {
+                        Some(false)
+                    } else {
+                        None
+                    }
+                }).inner
+            }
+            PairingPrompt::Display { passkey, .. } => {
+                ui.label(format!("Type this passkey on {name}, then press enter on it"));
+                ui.label(RichText::new(format_passkey(*passkey)).heading().monospace());
+
+                ui.button("Close").clicked().then_some(false)
+            }
+        }
+    }
+
+    fn input_ui(&mut self, ui: &mut Ui, valid: bool) -> Option<bool> {
+        let resp = ui.text_edit_singleline(&mut self.input);
+        let submitted = resp.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+
+        resp.request_focus();
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(valid, egui::Button::new("Pair")).clicked() || (valid && submitted) {
+                Some(true)
+            } else if ui.button("Cancel").clicked() {
+                Some(false)
+            } else {
+                None
+            }
+        }).inner
+    }
+
+    fn answer(&mut self, accept: bool) {
+        let Some(prompt) = self.current.take() else {
+            return;
+        };
+
+        let input = std::mem::take(&mut self.input);
+
+        // the backend may have given up waiting, in which case there's no one to answer
+        match prompt {
+            PairingPrompt::Pin { reply, .. } => {
+                let _ = reply.send(accept.then_some(input));
+            }
+            PairingPrompt::Passkey { reply, .. } => {
+                let _ = reply.send(parse_passkey(&input).filter(|_| accept));
+            }
+            PairingPrompt::Confirm { reply, .. } => {
+                let _ = reply.send(accept);
+            }
+            PairingPrompt::Display { .. } => {}
+        }
+    }
+}
+
+/// Passkeys are at most 6 digits
+fn parse_passkey(input: &str) -> Option<u32> {
+    input.trim().parse().ok().filter(|&passkey| passkey <= 999_999)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use eframe::egui;
+    use windows_bluetooth::{BluetoothBackend, ErrorKind, MockBackend, MockDevice, MockPairing, PairingAgent};
+    use crate::test_devices::KEYBOARD;
+    use super::{PairingDialog, PairingPrompt};
+
+    /// Waits for the backend's request to reach the dialog
+    fn wait_for_prompt(dialog: &mut PairingDialog) {
+        let start = Instant::now();
+
+        while !dialog.is_open() {
+            assert!(start.elapsed() < Duration::from_secs(5), "no pairing request arrived");
+
+            thread::sleep(Duration::from_millis(1));
+            dialog.poll();
+        }
+    }
+
+    fn connect_with_dialog(pairing: MockPairing, answer: impl FnOnce(&mut PairingDialog)) -> Result<(), ErrorKind> {
+        let (mut dialog, agent) = PairingDialog::new(egui::Context::default());
+
+        let backend = Arc::new(MockBackend::with_devices([MockDevice::new(KEYBOARD)]));
+        backend.set_pairing(KEYBOARD, pairing);
+        backend.set_pairing_agent(Arc::new(agent));
+
+        let connecting = thread::spawn({
+            let backend = backend.clone();
+            move || backend.connect_to_device(KEYBOARD).map_err(|err| err.kind())
+        });
+
+        wait_for_prompt(&mut dialog);
+        assert_eq!(dialog.mac_address(), Some(KEYBOARD));
+
+        answer(&mut dialog);
+
+        connecting.join().unwrap()
+    }
+
+    #[test]
+    fn answered_prompts_pair() {
+        let res = connect_with_dialog(MockPairing::Pin("0000".into()), |dialog| {
+            dialog.input = "0000".into();
+            dialog.answer(true);
+        });
+        assert_eq!(res, Ok(()));
+
+        let res = connect_with_dialog(MockPairing::EnterPasskey(4321), |dialog| {
+            dialog.input = " 004321 ".into();
+            dialog.answer(true);
+        });
+        assert_eq!(res, Ok(()));
+
+        let res = connect_with_dialog(MockPairing::ConfirmPasskey(123456), |dialog| dialog.answer(true));
+        assert_eq!(res, Ok(()));
+
+        // nothing to answer, pairing goes on while the passkey is shown
+        let res = connect_with_dialog(MockPairing::DisplayPasskey(98765), |dialog| {
+            assert!(matches!(dialog.current, Some(PairingPrompt::Display { passkey: 98765, .. })));
+        });
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn declined_prompts_cancel() {
+        let res = connect_with_dialog(MockPairing::ConfirmPasskey(123456), |dialog| dialog.answer(false));
+        assert_eq!(res, Err(ErrorKind::Cancelled));
+
+        let res = connect_with_dialog(MockPairing::Pin("0000".into()), |dialog| {
+            dialog.input = "1234".into();
+            dialog.answer(true);
+        });
+        assert_eq!(res, Err(ErrorKind::Cancelled));
+    }
+
+    #[test]
+    fn dropped_reply_rejects_instead_of_blocking() {
+        let res = connect_with_dialog(MockPairing::Pin("0000".into()), |dialog| {
+            // as if the app closed with the prompt open
+            dialog.current = None;
+        });
+
+        assert_eq!(res, Err(ErrorKind::Cancelled));
+    }
+
+    #[test]
+    fn unanswered_prompt_times_out() {
+        let (mut dialog, agent) = PairingDialog::new(egui::Context::default());
+
+        let asking = thread::spawn(move || agent.ask_within(Duration::from_millis(20), |reply| PairingPrompt::Confirm { mac_address: KEYBOARD, passkey: 1, reply }));
+
+        assert_eq!(asking.join().unwrap(), None);
+
+        // answering late is ignored
+        dialog.poll();
+        dialog.answer(true);
+    }
+
+    #[test]
+    fn closed_dialog_rejects_right_away() {
+        let (dialog, agent) = PairingDialog::new(egui::Context::default());
+
+        drop(dialog);
+
+        assert_eq!(agent.request_pin(KEYBOARD), None);
+        assert!(!agent.confirm_passkey(KEYBOARD, 1));
+    }
+}
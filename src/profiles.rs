@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io};
+use serde::{Deserialize, Serialize};
+use windows_bluetooth::MacAddress;
+use crate::timeout::ExpiryAction;
+
+/// Environment variable with the path to keep remembered timeouts at, instead of the user's config directory
+pub const PROFILES_ENV_VAR: &str = "BLUETOOTH_TIMEOUT_PROFILES";
+
+/// A device's remembered timeout, which it starts with whenever it connects.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimeoutProfile {
+    /// The timeout as typed, e.g. `1h` or `23:00`, so a time of day means the next one after connecting
+    pub timeout: Option<String>,
+    pub idle: bool,
+    #[serde(with = "humantime_secs")]
+    pub warn_before: Option<Duration>,
+    pub expiry_action: ExpiryAction,
+    pub act_on_close: bool,
+}
+
+/// Remembered timeouts by device, saved as JSON.
+#[derive(Debug, Default)]
+pub struct Profiles {
+    /// Where the profiles are saved, or `None` to only keep them in memory
+    path: Option<PathBuf>,
+    profiles: HashMap<MacAddress, TimeoutProfile>,
+}
+
+impl Profiles {
+    /// Loads the profiles at `path`, which starts out empty if the file doesn't exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+
+        let profiles = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self { path: Some(path), profiles })
+    }
+
+    /// Loads the profiles from the path set in [`PROFILES_ENV_VAR`], or the user's config directory.
+    ///
+    /// Profiles which can't be loaded are logged, and only kept in memory so the broken file isn't overwritten.
+    pub fn from_env() -> Self {
+        let Some(path) = env::var_os(PROFILES_ENV_VAR).map(PathBuf::from).or_else(default_path) else {
+            tracing::warn!("No config directory to remember timeouts in, set {PROFILES_ENV_VAR} to a file path");
+            return Self::default();
+        };
+
+        Self::load(&path).unwrap_or_else(|err| {
+            tracing::error!("Failed to load remembered timeouts from {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn get(&self, mac_address: MacAddress) -> Option<&TimeoutProfile> {
+        self.profiles.get(&mac_address)
+    }
+
+    /// Remembers the device's timeout and saves the profiles, unless it's already remembered as is.
+    pub fn remember(&mut self, mac_address: MacAddress, profile: TimeoutProfile) -> io::Result<()> {
+        if self.profiles.get(&mac_address) == Some(&profile) {
+            return Ok(());
+        }
+
+        self.profiles.insert(mac_address, profile);
+        self.save()
+    }
+
+    /// Forgets the device's timeout and saves the profiles, if it was remembered.
+    pub fn forget(&mut self, mac_address: MacAddress) -> io::Result<()> {
+        match self.profiles.remove(&mac_address) {
+            Some(_) => self.save(),
+            None => Ok(()),
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, serde_json::to_vec_pretty(&self.profiles)?)
+    }
+}
+
+#[cfg(windows)]
+fn default_path() -> Option<PathBuf> {
+    let app_data = env::var_os("APPDATA")?;
+
+    Some(Path::new(&app_data).join("BluetoothTimeout").join("profiles.json"))
+}
+
+#[cfg(not(windows))]
+fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config.join("bluetooth_timeout").join("profiles.json"))
+}
+
+/// Durations kept readable in the saved file, e.g. `2m`
+mod humantime_secs {
+    use std::time::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.collect_str(&humantime::format_duration(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|duration| humantime::parse_duration(&duration).map_err(Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use std::{env, fs, process};
+    use chrono::{Local, TimeZone};
+    use crate::test_devices::{HEADPHONES, KEYBOARD};
+    use crate::timeout::{Expiry, ExpiryAction, Timeout};
+    use super::{Profiles, TimeoutProfile};
+
+    /// A path of the test's own in the temp directory, with nothing at it yet
+    fn temp_path(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bluetooth_timeout-{test}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir.join("profiles.json")
+    }
+
+    fn profile(timeout: &str) -> TimeoutProfile {
+        TimeoutProfile {
+            timeout: Some(timeout.to_string()),
+            idle: true,
+            warn_before: Some(Duration::from_secs(2 * 60)),
+            expiry_action: ExpiryAction::Disconnect,
+            act_on_close: false,
+        }
+    }
+
+    #[test]
+    fn saved_profiles_load_back() {
+        let path = temp_path("round-trip");
+
+        let mut profiles = Profiles::load(&path).unwrap();
+        assert_eq!(profiles.get(HEADPHONES), None);
+
+        profiles.remember(HEADPHONES, profile("1h 30m")).unwrap();
+        profiles.remember(KEYBOARD, TimeoutProfile { warn_before: None, ..profile("23:00") }).unwrap();
+
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(json.contains(r#""warn_before": "2m""#), "{json}");
+
+        let mut loaded = Profiles::load(&path).unwrap();
+        assert_eq!(loaded.get(HEADPHONES), Some(&profile("1h 30m")));
+        assert_eq!(loaded.get(KEYBOARD), Some(&TimeoutProfile { warn_before: None, ..profile("23:00") }));
+
+        loaded.forget(KEYBOARD).unwrap();
+
+        let loaded = Profiles::load(&path).unwrap();
+        assert_eq!(loaded.get(HEADPHONES), Some(&profile("1h 30m")));
+        assert_eq!(loaded.get(KEYBOARD), None);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn broken_profiles_fail_to_load() {
+        let path = temp_path("broken");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(Profiles::load(&path).is_err());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn times_of_day_are_remembered_without_their_date() {
+        let now = Local.with_ymd_and_hms(2026, 6, 10, 20, 0, 0).unwrap();
+        let at = Expiry::At(Local.with_ymd_and_hms(2026, 6, 10, 23, 0, 0).unwrap());
+
+        let timeout = Timeout { expiry: Some(at), ..Timeout::default_from(HEADPHONES) };
+        let remembered = timeout.profile();
+        assert_eq!(remembered.timeout.as_deref(), Some("23:00"));
+
+        let path = temp_path("time-of-day");
+        Profiles::load(&path).unwrap().remember(HEADPHONES, remembered).unwrap();
+
+        let loaded = Profiles::load(&path).unwrap();
+        let timeout = loaded.get(HEADPHONES).and_then(|profile| profile.timeout.as_deref()).unwrap();
+        assert_eq!(Expiry::parse(timeout, now), Some(at));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
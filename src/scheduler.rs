@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use windows_bluetooth::{ActivitySource, MacAddress};
+use crate::timeout::ExpiryAction;
+
+/// Source of the current time for a [`TimeoutScheduler`].
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when advanced, so timeouts can be expired without waiting for them.
+///
+/// The background thread of [`TimeoutScheduler::spawn`] still waits in real time, so a scheduler on this clock
+/// should be driven with [`TimeoutScheduler::take_expired`] instead, or [woken](TimeoutScheduler::wake) after advancing it.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self { now: Mutex::new(Instant::now()) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("manual clock shouldn't be poisoned") += by;
+    }
+}
+
+#[cfg(test)]
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("manual clock shouldn't be poisoned")
+    }
+}
+
+impl<C: Clock> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    at: Instant,
+    action: ExpiryAction,
+    /// For idle timeouts, how long the device has to go unused, which pushes `at` back whenever it's used
+    idle: Option<Duration>,
+    /// How long before `at` to warn that the device is about to time out
+    warn_before: Option<Duration>,
+    /// The deadline a warning was last given for, so it's only given again once the deadline moves
+    warned_for: Option<Instant>,
+}
+
+impl Deadline {
+    /// When the scheduler next has to look at the deadline, either to warn or to expire it
+    fn next_wake(&self) -> Instant {
+        match self.warn_before {
+            Some(warn_before) if self.warned_for != Some(self.at) => self.at.checked_sub(warn_before).unwrap_or(self.at),
+            _ => self.at,
+        }
+    }
+}
+
+/// What happened to a device's deadline, handed to the callback of [`TimeoutScheduler::spawn`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeoutEvent {
+    /// The device times out in `remaining`, unless its deadline is extended
+    Warning { mac_address: MacAddress, remaining: Duration },
+    Expired { mac_address: MacAddress, action: ExpiryAction },
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    deadlines: HashMap<MacAddress, Deadline>,
+    stopped: bool,
+}
+
+impl SchedulerState {
+    fn next_wake(&self) -> Option<Instant> {
+        self.deadlines.values().map(Deadline::next_wake).min()
+    }
+}
+
+/// Deadlines for each device, and what to do with the device once its deadline passes.
+///
+/// Clones share the same deadlines. Expired deadlines are either taken with [`take_expired`](Self::take_expired),
+/// or handed to a callback on a background thread with [`spawn`](Self::spawn), which doesn't depend on the UI repainting.
+#[derive(Clone)]
+pub struct TimeoutScheduler {
+    clock: Arc<dyn Clock>,
+    activity: Option<Arc<dyn ActivitySource>>,
+    state: Arc<(Mutex<SchedulerState>, Condvar)>,
+}
+
+impl TimeoutScheduler {
+    pub fn new(clock: impl Clock) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            activity: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Restarts idle timeouts whenever `activity` reports their device was used.
+    ///
+    /// The activity only reports how long a device has been idle, which is counted back from the scheduler's clock.
+    pub fn with_activity(mut self, activity: Arc<dyn ActivitySource>) -> Self {
+        self.activity = Some(activity);
+        self
+    }
+
+    /// Whether idle timeouts can tell when their device is used, without which they're plain countdowns
+    pub fn tracks_activity(&self) -> bool {
+        self.activity.is_some()
+    }
+
+    /// Expires the device `duration` from now, replacing its earlier deadline if it had one.
+    pub fn schedule(&self, mac_address: MacAddress, duration: Duration, action: ExpiryAction) {
+        self.insert(mac_address, duration, action, None);
+    }
+
+    /// Expires the device once it hasn't been used for `idle`, replacing its earlier deadline if it had one.
+    pub fn schedule_idle(&self, mac_address: MacAddress, idle: Duration, action: ExpiryAction) {
+        self.insert(mac_address, idle, action, Some(idle));
+    }
+
+    /// Changes what happens once the device's deadline passes, keeping the deadline itself.
+    pub fn set_action(&self, mac_address: MacAddress, action: ExpiryAction) {
+        if let Some(deadline) = self.state().deadlines.get_mut(&mac_address) {
+            deadline.action = action;
+        }
+    }
+
+    /// Warns `warn_before` the device's deadline passes, or never for `None`.
+    ///
+    /// A deadline which is already closer than that isn't warned about, since it was just set.
+    pub fn set_warning(&self, mac_address: MacAddress, warn_before: Option<Duration>) {
+        let now = self.clock.now();
+
+        self.update(|state| {
+            if let Some(deadline) = state.deadlines.get_mut(&mac_address) {
+                deadline.warn_before = warn_before;
+                deadline.warned_for = warn_before
+                    .filter(|&warn_before| deadline.at.saturating_duration_since(now) <= warn_before)
+                    .map(|_| deadline.at);
+            }
+        });
+    }
+
+    /// Moves the device's deadline `by` later, which can be warned about again, returning whether it had one.
+    pub fn extend(&self, mac_address: MacAddress, by: Duration) -> bool {
+        self.update(|state| {
+            let Some(deadline) = state.deadlines.get_mut(&mac_address) else {
+                return false;
+            };
+
+            deadline.at = self.deadline_at(mac_address, *deadline) + by;
+
+            true
+        })
+    }
+
+    /// Removes the device's deadline, returning whether it had one.
+    pub fn cancel(&self, mac_address: MacAddress) -> bool {
+        self.update(|state| state.deadlines.remove(&mac_address).is_some())
+    }
+
+    /// Time left until the device's deadline, if it has one
+    pub fn remaining(&self, mac_address: MacAddress) -> Option<Duration> {
+        let deadline = *self.state().deadlines.get(&mac_address)?;
+
+        Some(self.deadline_at(mac_address, deadline).saturating_duration_since(self.clock.now()))
+    }
+
+    /// Removes the deadlines which have passed, earliest first, returning what to do with each device.
+    pub fn take_expired(&self) -> Vec<(MacAddress, ExpiryAction)> {
+        let now = self.clock.now();
+        let mut state = self.state();
+
+        // idle timeouts whose device was used since are pushed back instead
+        for (&mac_address, deadline) in state.deadlines.iter_mut().filter(|(_, deadline)| deadline.at <= now) {
+            deadline.at = self.deadline_at(mac_address, *deadline);
+        }
+
+        let mut expired = state.deadlines
+            .extract_if(|_, deadline| deadline.at <= now)
+            .collect::<Vec<_>>();
+
+        expired.sort_by_key(|(_, deadline)| deadline.at);
+
+        expired.into_iter()
+            .map(|(mac_address, deadline)| (mac_address, deadline.action))
+            .collect()
+    }
+
+    /// Marks the deadlines which are close enough to warn about as warned, returning how long each device has left.
+    pub fn take_warnings(&self) -> Vec<(MacAddress, Duration)> {
+        let now = self.clock.now();
+        let mut state = self.state();
+        let mut warnings = Vec::new();
+
+        for (&mac_address, deadline) in state.deadlines.iter_mut().filter(|(_, deadline)| deadline.next_wake() <= now) {
+            // idle timeouts whose device was used since are pushed back, and warned about once they're close again
+            deadline.at = self.deadline_at(mac_address, *deadline);
+
+            let Some(warn_before) = deadline.warn_before else {
+                continue;
+            };
+
+            let remaining = deadline.at.saturating_duration_since(now);
+
+            // an expired deadline is taken by take_expired instead
+            if !remaining.is_zero() && remaining <= warn_before && deadline.warned_for != Some(deadline.at) {
+                deadline.warned_for = Some(deadline.at);
+                warnings.push((mac_address, remaining));
+            }
+        }
+
+        warnings
+    }
+
+    /// Calls `on_event` on a background thread as each deadline is close to passing, and passes, until the scheduler is [stopped](Self::stop).
+    pub fn spawn(&self, mut on_event: impl FnMut(TimeoutEvent) + Send + 'static) -> JoinHandle<()> {
+        let scheduler = self.clone();
+
+        thread::spawn(move || {
+            let (_, changed) = &*scheduler.state;
+
+            loop {
+                // called without holding the lock, so the callback can schedule again
+                for (mac_address, action) in scheduler.take_expired() {
+                    on_event(TimeoutEvent::Expired { mac_address, action });
+                }
+
+                for (mac_address, remaining) in scheduler.take_warnings() {
+                    on_event(TimeoutEvent::Warning { mac_address, remaining });
+                }
+
+                let state = scheduler.state();
+
+                if state.stopped {
+                    return;
+                }
+
+                // woken early whenever the deadlines change
+                let _state = match state.next_wake() {
+                    Some(at) => {
+                        let timeout = at.saturating_duration_since(scheduler.clock.now());
+
+                        changed.wait_timeout(state, timeout).expect("scheduler state shouldn't be poisoned").0
+                    }
+                    None => changed.wait(state).expect("scheduler state shouldn't be poisoned"),
+                };
+            }
+        })
+    }
+
+    /// Makes the background thread look at the deadlines again, for when the clock jumped instead of passing.
+    #[cfg(test)]
+    pub fn wake(&self) {
+        self.update(|_| ());
+    }
+
+    /// Ends the background thread, without firing the remaining deadlines.
+    pub fn stop(&self) {
+        self.update(|state| state.stopped = true);
+    }
+
+    fn insert(&self, mac_address: MacAddress, duration: Duration, action: ExpiryAction, idle: Option<Duration>) {
+        let at = self.clock.now() + duration;
+
+        self.update(|state| {
+            state.deadlines.insert(mac_address, Deadline { at, action, idle, warn_before: None, warned_for: None });
+        });
+    }
+
+    /// When the deadline passes, taking into account when an idle timeout's device was last used
+    fn deadline_at(&self, mac_address: MacAddress, deadline: Deadline) -> Instant {
+        let last_active = self.activity.as_ref()
+            .and_then(|activity| activity.idle_for(mac_address))
+            .and_then(|idle_for| self.clock.now().checked_sub(idle_for));
+
+        match (deadline.idle, last_active) {
+            (Some(idle), Some(last_active)) => deadline.at.max(last_active + idle),
+            _ => deadline.at,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state.0.lock().expect("scheduler state shouldn't be poisoned")
+    }
+
+    /// Changes the state and wakes the background thread, so it waits for the new next deadline
+    fn update<T>(&self, f: impl FnOnce(&mut SchedulerState) -> T) -> T {
+        let res = f(&mut self.state());
+
+        self.state.1.notify_all();
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use windows_bluetooth::MockActivity;
+    use crate::test_devices::{HEADPHONES, KEYBOARD};
+    use crate::timeout::ExpiryAction;
+    use super::{Clock, ManualClock, SystemClock, TimeoutEvent, TimeoutScheduler};
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn manual_scheduler() -> (Arc<ManualClock>, TimeoutScheduler) {
+        let clock = Arc::new(ManualClock::new());
+
+        (clock.clone(), TimeoutScheduler::new(clock))
+    }
+
+    #[test]
+    fn expires_once_the_deadline_passes() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+
+        clock.advance(9 * MINUTE);
+        assert_eq!(scheduler.take_expired(), []);
+        assert_eq!(scheduler.remaining(HEADPHONES), Some(MINUTE));
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+        assert_eq!(scheduler.remaining(HEADPHONES), None);
+        assert_eq!(scheduler.take_expired(), []);
+    }
+
+    #[test]
+    fn expires_earliest_first() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, 2 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.schedule(KEYBOARD, MINUTE, ExpiryAction::Remove);
+
+        clock.advance(5 * MINUTE);
+        assert_eq!(scheduler.take_expired(), [(KEYBOARD, ExpiryAction::Remove), (HEADPHONES, ExpiryAction::Disconnect)]);
+    }
+
+    #[test]
+    fn rescheduling_replaces_the_deadline() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, MINUTE, ExpiryAction::Disconnect);
+        scheduler.schedule(HEADPHONES, 3 * MINUTE, ExpiryAction::Remove);
+
+        clock.advance(2 * MINUTE);
+        assert_eq!(scheduler.take_expired(), []);
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Remove)]);
+    }
+
+    #[test]
+    fn extend_set_action_and_cancel() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, MINUTE, ExpiryAction::Disconnect);
+
+        assert!(scheduler.extend(HEADPHONES, MINUTE));
+        assert!(!scheduler.extend(KEYBOARD, MINUTE));
+        scheduler.set_action(HEADPHONES, ExpiryAction::Remove);
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_expired(), []);
+        assert_eq!(scheduler.remaining(HEADPHONES), Some(MINUTE));
+
+        assert!(scheduler.cancel(HEADPHONES));
+        assert!(!scheduler.cancel(HEADPHONES));
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_expired(), []);
+
+        scheduler.schedule(KEYBOARD, MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_action(KEYBOARD, ExpiryAction::Remove);
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_expired(), [(KEYBOARD, ExpiryAction::Remove)]);
+    }
+
+    #[test]
+    fn warns_once_per_deadline() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_warning(HEADPHONES, Some(2 * MINUTE));
+
+        clock.advance(7 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), []);
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_warnings(), [(HEADPHONES, 2 * MINUTE)]);
+
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_warnings(), []);
+
+        // an expired deadline isn't warned about, only taken
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_warnings(), []);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+    }
+
+    #[test]
+    fn warns_again_after_extending() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_warning(HEADPHONES, Some(2 * MINUTE));
+
+        clock.advance(9 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), [(HEADPHONES, MINUTE)]);
+
+        assert!(scheduler.extend(HEADPHONES, 5 * MINUTE));
+        assert_eq!(scheduler.take_warnings(), []);
+
+        clock.advance(4 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), [(HEADPHONES, 2 * MINUTE)]);
+        assert_eq!(scheduler.take_warnings(), []);
+    }
+
+    #[test]
+    fn doesnt_warn_about_deadlines_already_within_the_lead() {
+        let (clock, scheduler) = manual_scheduler();
+        scheduler.schedule(HEADPHONES, MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_warning(HEADPHONES, Some(2 * MINUTE));
+        scheduler.schedule(KEYBOARD, 10 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_warning(KEYBOARD, None);
+
+        assert_eq!(scheduler.take_warnings(), []);
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(scheduler.take_warnings(), []);
+
+        // without a warning set, nothing is said until it expires
+        clock.advance(9 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), []);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+
+        // 30s left is within the lead, but an extension past it is warned about as it comes close again
+        scheduler.set_warning(KEYBOARD, Some(2 * MINUTE));
+        assert_eq!(scheduler.take_warnings(), []);
+        assert!(scheduler.extend(KEYBOARD, 5 * MINUTE));
+
+        clock.advance(Duration::from_secs(3 * 60 + 30));
+        assert_eq!(scheduler.take_warnings(), [(KEYBOARD, 2 * MINUTE)]);
+    }
+
+    #[test]
+    fn background_thread_warns_before_expiring() {
+        let scheduler = TimeoutScheduler::new(SystemClock);
+        let (events, received) = mpsc::channel();
+
+        let thread = scheduler.spawn(move |event| events.send(event).unwrap());
+        scheduler.schedule(HEADPHONES, Duration::from_millis(200), ExpiryAction::Remove);
+        scheduler.set_warning(HEADPHONES, Some(Duration::from_millis(100)));
+
+        assert!(matches!(
+            received.recv_timeout(Duration::from_secs(5)),
+            Ok(TimeoutEvent::Warning { mac_address: HEADPHONES, .. }),
+        ));
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)),
+            Ok(TimeoutEvent::Expired { mac_address: HEADPHONES, action: ExpiryAction::Remove }),
+        );
+
+        scheduler.stop();
+        thread.join().unwrap();
+    }
+
+    /// A scheduler whose activity is told on the same manual clock
+    fn idle_scheduler() -> (Arc<ManualClock>, MockActivity, TimeoutScheduler) {
+        let clock = Arc::new(ManualClock::new());
+        let activity = MockActivity::with_clock({
+            let clock = clock.clone();
+            move || clock.now()
+        });
+
+        let scheduler = TimeoutScheduler::new(clock.clone()).with_activity(Arc::new(activity.clone()));
+
+        (clock, activity, scheduler)
+    }
+
+    #[test]
+    fn activity_pushes_back_idle_deadlines() {
+        let (clock, activity, scheduler) = idle_scheduler();
+        scheduler.schedule_idle(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+
+        clock.advance(4 * MINUTE);
+        activity.touch(HEADPHONES);
+        assert_eq!(scheduler.remaining(HEADPHONES), Some(10 * MINUTE));
+
+        clock.advance(6 * MINUTE);
+        assert_eq!(scheduler.take_expired(), []);
+        assert_eq!(scheduler.remaining(HEADPHONES), Some(4 * MINUTE));
+
+        clock.advance(4 * MINUTE);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+    }
+
+    #[test]
+    fn playing_devices_never_go_idle() {
+        let (clock, activity, scheduler) = idle_scheduler();
+        scheduler.schedule_idle(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.schedule(KEYBOARD, 10 * MINUTE, ExpiryAction::Disconnect);
+
+        activity.start_playing(HEADPHONES);
+        activity.touch(KEYBOARD);
+
+        clock.advance(60 * MINUTE);
+        assert_eq!(scheduler.take_expired(), [(KEYBOARD, ExpiryAction::Disconnect)]);
+
+        activity.stop_playing(HEADPHONES);
+
+        clock.advance(10 * MINUTE);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+    }
+
+    #[test]
+    fn moved_idle_deadlines_are_warned_about_again() {
+        let (clock, activity, scheduler) = idle_scheduler();
+        scheduler.schedule_idle(HEADPHONES, 10 * MINUTE, ExpiryAction::Disconnect);
+        scheduler.set_warning(HEADPHONES, Some(2 * MINUTE));
+
+        clock.advance(8 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), [(HEADPHONES, 2 * MINUTE)]);
+
+        activity.touch(HEADPHONES);
+        clock.advance(MINUTE);
+        assert_eq!(scheduler.take_warnings(), []);
+        assert_eq!(scheduler.take_expired(), []);
+
+        clock.advance(7 * MINUTE);
+        assert_eq!(scheduler.take_warnings(), [(HEADPHONES, 2 * MINUTE)]);
+
+        clock.advance(2 * MINUTE);
+        assert_eq!(scheduler.take_expired(), [(HEADPHONES, ExpiryAction::Disconnect)]);
+    }
+
+    #[test]
+    fn background_thread_fires_and_stops() {
+        let scheduler = TimeoutScheduler::new(SystemClock);
+        let (events, received) = mpsc::channel();
+
+        let thread = scheduler.spawn(move |event| events.send(event).unwrap());
+        scheduler.schedule(HEADPHONES, Duration::from_millis(10), ExpiryAction::Disconnect);
+
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)),
+            Ok(TimeoutEvent::Expired { mac_address: HEADPHONES, action: ExpiryAction::Disconnect }),
+        );
+
+        scheduler.stop();
+        thread.join().unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use eframe::egui::{Button, Response, Ui, Widget};
-use crate::app::BluetoothApp;
+use crate::app::{BluetoothApp, DEEP_SCAN_DURATION};
 
 pub struct RescanButtonSpinner<'a> {
     pub app: &'a mut BluetoothApp,
@@ -7,16 +7,28 @@ pub struct RescanButtonSpinner<'a> {
 
 impl Widget for RescanButtonSpinner<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
-        let mut response = ui.add_enabled(self.app.scan_recv.is_none(), Button::new("Rescan"));
+        let mut response = ui.add_enabled(self.app.scan_task.is_none(), Button::new("Rescan"));
 
         if response.clicked() {
             self.app.start_scan();
         }
 
-        if self.app.scan_recv.is_some() {
+        let deep_scan = ui.add_enabled(self.app.scan_task.is_none(), Button::new("Deep Scan"))
+            .on_hover_text(format!(
+                "Searches for devices in range, like ones in pairing mode, which takes about {} seconds",
+                DEEP_SCAN_DURATION.as_secs(),
+            ));
+
+        if deep_scan.clicked() {
+            self.app.start_deep_scan();
+        }
+
+        response |= deep_scan;
+
+        if self.app.scan_task.is_some() {
             response |= ui.spinner();
         }
 
         response
     }
-}
\ No newline at end of file
+}
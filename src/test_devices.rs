@@ -0,0 +1,6 @@
+//! Devices shared by the app's tests, matching those of the library's tests.
+
+use windows_bluetooth::{mac, MacAddress};
+
+pub const HEADPHONES: MacAddress = mac!("00:1A:7D:DA:71:13");
+pub const KEYBOARD: MacAddress = mac!("F4:4E:FD:63:0A:91");
@@ -1,30 +1,304 @@
+use std::fmt::{Display, Formatter};
 use std::time::Duration;
+use chrono::{DateTime, Local, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
 use windows_bluetooth::MacAddress;
+use crate::profiles::{Profiles, TimeoutProfile};
+use crate::scheduler::TimeoutScheduler;
 
+/// What to do with a device once its timeout runs out, or the application closes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ExpiryAction {
+    /// Drops the connection, but keeps the device paired
+    Disconnect,
+    /// Unpairs the device, which then has to be put into pairing mode to connect again
+    Remove,
+}
+
+impl ExpiryAction {
+    pub const ALL: [ExpiryAction; 2] = [ExpiryAction::Disconnect, ExpiryAction::Remove];
+}
+
+impl Display for ExpiryAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpiryAction::Disconnect => write!(f, "Disconnect"),
+            ExpiryAction::Remove => write!(f, "Remove"),
+        }
+    }
+}
+
+/// How a connected device is handled once it times out, with its deadline kept by the [`TimeoutScheduler`](crate::scheduler::TimeoutScheduler).
 pub struct Timeout {
     pub mac_address: MacAddress,
-    pub duration: Option<Duration>,
-    pub remove_on_close: bool,
+    /// When the timeout was set to run out, kept after it has
+    pub expiry: Option<Expiry>,
+    /// Count down only while the device isn't used, starting over whenever it is
+    pub idle: bool,
+    /// How long before expiring to warn, with the option to extend the timeout
+    pub warn_before: Option<Duration>,
+    pub expiry_action: ExpiryAction,
+    /// Apply the expiry action when the application closes
+    pub act_on_close: bool,
 }
 
 impl Timeout {
     pub fn default_from(mac_address: MacAddress) -> Self {
         Self {
             mac_address,
-            duration: None,
-            remove_on_close: true,
+            expiry: None,
+            idle: false,
+            warn_before: Some(Duration::from_secs(2 * 60)),
+            expiry_action: ExpiryAction::Remove,
+            act_on_close: true,
+        }
+    }
+
+    /// Starts out with the device's remembered timeout if it has one
+    pub fn for_device(mac_address: MacAddress, profiles: &Profiles) -> Self {
+        match profiles.get(mac_address) {
+            Some(profile) => Self::from_profile(mac_address, profile),
+            None => Self::default_from(mac_address),
+        }
+    }
+
+    /// Starts out with the device's remembered timeout, which is unset if it no longer parses
+    pub fn from_profile(mac_address: MacAddress, profile: &TimeoutProfile) -> Self {
+        Self {
+            mac_address,
+            expiry: profile.timeout.as_deref().and_then(|timeout| Expiry::parse(timeout, Local::now())),
+            idle: profile.idle,
+            warn_before: profile.warn_before,
+            expiry_action: profile.expiry_action,
+            act_on_close: profile.act_on_close,
+        }
+    }
+
+    /// The timeout to remember for the device, where a time of day is remembered without its date
+    pub fn profile(&self) -> TimeoutProfile {
+        let timeout = self.expiry.map(|expiry| match expiry {
+            Expiry::After(duration) => duration_str(duration),
+            Expiry::At(at) => at.format("%H:%M").to_string(),
+        });
+
+        TimeoutProfile {
+            timeout,
+            idle: self.idle,
+            warn_before: self.warn_before,
+            expiry_action: self.expiry_action,
+            act_on_close: self.act_on_close,
+        }
+    }
+
+    /// Whether the timeout counts down only while the device isn't used, which a time of day can't
+    pub fn is_idle(&self) -> bool {
+        self.idle && matches!(self.expiry, Some(Expiry::After(_)))
+    }
+
+    /// Starts the timeout over with the scheduler, or cancels it if there's no expiry
+    pub fn start(&self, scheduler: &TimeoutScheduler) {
+        match self.expiry {
+            Some(Expiry::After(idle)) if self.idle => scheduler.schedule_idle(self.mac_address, idle, self.expiry_action),
+            Some(expiry) => scheduler.schedule(self.mac_address, expiry.remaining_from(Local::now()), self.expiry_action),
+            None => {
+                scheduler.cancel(self.mac_address);
+                return;
+            }
+        }
+
+        scheduler.set_warning(self.mac_address, self.warn_before);
+    }
+
+    /// Moves the timeout `by` later
+    pub fn extend(&mut self, scheduler: &TimeoutScheduler, by: Duration) {
+        if !scheduler.extend(self.mac_address, by) {
+            return;
+        }
+
+        // a countdown's time of day is worked out from the time left instead
+        if let Some(Expiry::At(at)) = &mut self.expiry {
+            *at += by;
+        }
+    }
+
+    /// Formats the time left along with the time of day the timeout runs out, e.g. `1h 30m (23:00)`
+    pub fn remaining_str(&self, remaining: Option<Duration>) -> String {
+        let Some(remaining) = remaining else {
+            return "None".into();
+        };
+
+        if self.is_idle() {
+            return format!("{} idle", duration_str(remaining));
+        }
+
+        let now = Local::now();
+
+        let at = match self.expiry {
+            Some(Expiry::At(at)) => at,
+            _ => now + remaining,
+        };
+
+        format!("{} ({})", duration_str(remaining), clock_time_str(at, now))
+    }
+
+    /// The timeout as it would be typed, which for a countdown is the time left, and for an idle timeout how long it idles for
+    pub fn input_str(&self, remaining: Option<Duration>) -> String {
+        match (self.expiry, remaining) {
+            (Some(Expiry::At(at)), Some(_)) => clock_time_str(at, Local::now()),
+            (Some(Expiry::After(idle)), Some(_)) if self.idle => duration_str(idle),
+            (_, Some(remaining)) => duration_str(remaining),
+            (_, None) => String::new(),
         }
     }
+}
+
+/// When a timeout runs out, either after a countdown or at a time of day.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Expiry {
+    After(Duration),
+    At(DateTime<Local>),
+}
+
+/// How long before expiring a timeout can warn, from which one is picked when editing it
+pub const WARNING_LEADS: [Option<Duration>; 5] = [
+    None,
+    Some(Duration::from_secs(60)),
+    Some(Duration::from_secs(2 * 60)),
+    Some(Duration::from_secs(5 * 60)),
+    Some(Duration::from_secs(10 * 60)),
+];
+
+/// Formats accepted for a time of day, tried in order
+const TIME_FORMATS: [&str; 4] = ["%H:%M", "%H:%M:%S", "%I:%M%p", "%I:%M %p"];
+
+impl Expiry {
+    /// Parses a countdown like `1h 30m`, or a time of day like `23:00`, `11pm` or `tomorrow 08:00`.
+    ///
+    /// A time of day which has already passed today is taken to mean tomorrow.
+    pub fn parse(input: &str, now: DateTime<Local>) -> Option<Self> {
+        let input = input.trim().to_lowercase();
+
+        if let Ok(duration) = humantime::parse_duration(&input) {
+            return Some(Expiry::After(duration));
+        }
+
+        let input = input.strip_prefix("at ").unwrap_or(&input);
+
+        let (tomorrow, time) = match input.strip_prefix("tomorrow") {
+            Some(time) => (true, time.trim_start().strip_prefix("at ").unwrap_or(time.trim_start())),
+            None => (false, input),
+        };
+
+        // chrono needs the minutes, so `11pm` is read as `11:00pm`
+        let meridiem = ["am", "pm"].into_iter()
+            .find_map(|suffix| Some((time.strip_suffix(suffix)?, suffix)));
 
-    pub fn duration_str(&self) -> Option<String> {
-        self.duration
-            .map(|d|
-                humantime::format_duration(Duration::from_secs(d.as_secs())).to_string()
-            )
+        let time = match meridiem {
+            Some((hour, suffix)) if !hour.contains(':') => format!("{}:00{suffix}", hour.trim_end()),
+            _ => time.to_string(),
+        };
+
+        let time = TIME_FORMATS.iter()
+            .find_map(|format| NaiveTime::parse_from_str(&time, format).ok())?;
+
+        let at_on = |days| (now.date_naive() + TimeDelta::days(days))
+            .and_time(time)
+            .and_local_timezone(Local)
+            // the earlier of a time repeated when the clocks go back, while a time skipped when they go forward doesn't exist
+            .earliest();
+
+        let at = match tomorrow {
+            true => at_on(1)?,
+            false => at_on(0).filter(|&at| at > now).or_else(|| at_on(1))?,
+        };
+
+        Some(Expiry::At(at))
+    }
+
+    /// Time left from `now` until the timeout runs out, which is zero for a time of day that has passed.
+    pub fn remaining_from(&self, now: DateTime<Local>) -> Duration {
+        match *self {
+            Expiry::After(duration) => duration,
+            Expiry::At(at) => (at - now).to_std().unwrap_or(Duration::ZERO),
+        }
     }
+}
 
-    pub fn duration_str_or_none(&self) -> String {
-        self.duration_str()
-            .unwrap_or("None".into())
+/// Formats a time of day relative to `now`, e.g. `23:00`, `tomorrow 08:00` or `Sat 08:00`
+pub fn clock_time_str(at: DateTime<Local>, now: DateTime<Local>) -> String {
+    let days = (at.date_naive() - now.date_naive()).num_days();
+
+    match days {
+        0 => at.format("%H:%M").to_string(),
+        1 => at.format("tomorrow %H:%M").to_string(),
+        _ => at.format("%a %H:%M").to_string(),
     }
-}
\ No newline at end of file
+}
+
+/// Formats a timeout to the second, e.g. `1h 5m 3s`
+pub fn duration_str(duration: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(duration.as_secs())).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use chrono::{DateTime, Local, TimeZone};
+    use super::Expiry;
+
+    /// 20:00 on a day without a clock change
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 6, 10, 20, 0, 0).unwrap()
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> Expiry {
+        Expiry::At(Local.with_ymd_and_hms(2026, 6, day, hour, minute, 0).unwrap())
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        for (input, expiry) in [
+            ("23:00", at(10, 23, 0)),
+            ("23:00:00", at(10, 23, 0)),
+            ("11pm", at(10, 23, 0)),
+            ("11 pm", at(10, 23, 0)),
+            ("11:30 PM", at(10, 23, 30)),
+            ("at 23:00", at(10, 23, 0)),
+            ("  At 11pm ", at(10, 23, 0)),
+            ("tomorrow 08:00", at(11, 8, 0)),
+            ("tomorrow at 8am", at(11, 8, 0)),
+            ("tomorrow 23:00", at(11, 23, 0)),
+        ] {
+            assert_eq!(Expiry::parse(input, now()), Some(expiry), "{input}");
+        }
+    }
+
+    #[test]
+    fn passed_times_roll_over_to_tomorrow() {
+        assert_eq!(Expiry::parse("08:00", now()), Some(at(11, 8, 0)));
+        assert_eq!(Expiry::parse("7pm", now()), Some(at(11, 19, 0)));
+        // the current minute has passed as well
+        assert_eq!(Expiry::parse("20:00", now()), Some(at(11, 20, 0)));
+    }
+
+    #[test]
+    fn parses_countdowns() {
+        assert_eq!(Expiry::parse("1h 30m", now()), Some(Expiry::After(Duration::from_secs(90 * 60))));
+        assert_eq!(Expiry::parse("45s", now()), Some(Expiry::After(Duration::from_secs(45))));
+        assert_eq!(Expiry::parse("2H", now()), Some(Expiry::After(Duration::from_secs(2 * 60 * 60))));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        for input in ["", "soon", "25:00", "13pm", "tomorrow", "at", "yesterday 08:00", "1 hour and a bit"] {
+            assert_eq!(Expiry::parse(input, now()), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn remaining_time() {
+        assert_eq!(at(10, 23, 0).remaining_from(now()), Duration::from_secs(3 * 60 * 60));
+        assert_eq!(at(10, 19, 0).remaining_from(now()), Duration::ZERO);
+        assert_eq!(Expiry::After(Duration::from_secs(5)).remaining_from(now()), Duration::from_secs(5));
+    }
+}
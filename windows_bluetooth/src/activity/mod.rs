@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::MacAddress;
+
+#[cfg(windows)]
+mod win32;
+
+#[cfg(windows)]
+pub use win32::AudioSessionActivity;
+
+/// Reports how long devices have gone unused, e.g. without playing audio, for timing out devices once they go idle.
+///
+/// Idle time is a duration rather than an instant, so the source and whoever asks can each keep their own clock.
+pub trait ActivitySource: Send + Sync {
+    /// How long since the device was last seen in use, which is zero while it's in use, or `None` if it hasn't been
+    /// since the source started watching it.
+    fn idle_for(&self, mac_address: MacAddress) -> Option<Duration>;
+}
+
+impl<A: ActivitySource + ?Sized> ActivitySource for Arc<A> {
+    fn idle_for(&self, mac_address: MacAddress) -> Option<Duration> {
+        (**self).idle_for(mac_address)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockActivityState {
+    last_active: HashMap<MacAddress, Instant>,
+    playing: HashSet<MacAddress>,
+}
+
+/// An [`ActivitySource`] whose devices are only in use when told they are.
+///
+/// Clones share the same activity, so one can be handed to a scheduler while the other marks devices as used.
+#[derive(Clone)]
+pub struct MockActivity {
+    clock: Arc<dyn Fn() -> Instant + Send + Sync>,
+    state: Arc<Mutex<MockActivityState>>,
+}
+
+impl MockActivity {
+    pub fn new() -> Self {
+        Self::with_clock(Instant::now)
+    }
+
+    /// Tells the time with `clock` instead of the system's, e.g. a manual clock which also drives a scheduler.
+    pub fn with_clock(clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            state: Arc::default(),
+        }
+    }
+
+    /// Marks the device as last used at `at`, by the activity's clock
+    pub fn set_last_active(&self, mac_address: MacAddress, at: Instant) {
+        self.state().last_active.insert(mac_address, at);
+    }
+
+    /// Marks the device as used just now
+    pub fn touch(&self, mac_address: MacAddress) {
+        self.set_last_active(mac_address, (self.clock)());
+    }
+
+    /// Keeps the device in use until [`stop_playing`](Self::stop_playing), like a speaker playing music.
+    pub fn start_playing(&self, mac_address: MacAddress) {
+        self.state().playing.insert(mac_address);
+    }
+
+    /// Leaves the device last used now, if it was playing
+    pub fn stop_playing(&self, mac_address: MacAddress) {
+        if self.state().playing.remove(&mac_address) {
+            self.touch(mac_address);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockActivityState> {
+        self.state.lock().expect("mock activity shouldn't be poisoned")
+    }
+}
+
+impl Default for MockActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MockActivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockActivity")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ActivitySource for MockActivity {
+    fn idle_for(&self, mac_address: MacAddress) -> Option<Duration> {
+        let state = self.state();
+
+        match state.playing.contains(&mac_address) {
+            true => Some(Duration::ZERO),
+            false => state.last_active.get(&mac_address).map(|&at| (self.clock)().saturating_duration_since(at)),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use windows::core::Interface;
+use windows::Win32::Media::Audio::{eRender, AudioSessionStateActive, IAudioSessionManager2, IDeviceTopology, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator, DEVICE_STATE_ACTIVE};
+use windows::Win32::Media::Audio::Endpoints::IAudioMeterInformation;
+use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED};
+use crate::MacAddress;
+use super::ActivitySource;
+
+type LastActive = Mutex<HashMap<MacAddress, Instant>>;
+
+/// An [`ActivitySource`] which counts a bluetooth audio device as in use while any of its audio sessions is playing sound.
+///
+/// Sessions are sampled on a background thread, which ends once the source is dropped.
+#[derive(Debug, Clone)]
+pub struct AudioSessionActivity {
+    last_active: Arc<LastActive>,
+}
+
+impl AudioSessionActivity {
+    /// Starts sampling every `interval`, so a device's activity is reported up to `interval` late.
+    pub fn new(interval: Duration) -> Self {
+        let last_active = Arc::<LastActive>::default();
+        let weak = Arc::downgrade(&last_active);
+
+        thread::spawn(move || sample_while_alive(weak, interval));
+
+        Self { last_active }
+    }
+}
+
+impl ActivitySource for AudioSessionActivity {
+    fn idle_for(&self, mac_address: MacAddress) -> Option<Duration> {
+        self.last_active.lock().expect("audio activity shouldn't be poisoned").get(&mac_address).map(Instant::elapsed)
+    }
+}
+
+/// Uninitializes COM on the thread when dropped
+struct ComGuard;
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        // SAFETY: COM was initialized on this thread when the guard was created
+        unsafe { CoUninitialize() };
+    }
+}
+
+fn sample_while_alive(last_active: Weak<LastActive>, interval: Duration) {
+    // SAFETY: balanced by the guard's CoUninitialize
+    if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+        return;
+    }
+
+    let _com = ComGuard;
+
+    while let Some(last_active) = last_active.upgrade() {
+        // a device may disappear while it's being sampled, in which case it's sampled again next time
+        if let Ok(playing) = playing_devices() {
+            let now = Instant::now();
+            let mut last_active = last_active.lock().expect("audio activity shouldn't be poisoned");
+
+            for mac_address in playing {
+                last_active.insert(mac_address, now);
+            }
+        }
+
+        drop(last_active);
+        thread::sleep(interval);
+    }
+}
+
+/// The bluetooth devices with an audio session playing sound
+fn playing_devices() -> windows::core::Result<Vec<MacAddress>> {
+    // SAFETY: COM is initialized on the sampling thread
+    let enumerator: IMMDeviceEnumerator = unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+    let endpoints = unsafe { enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)? };
+
+    let mut playing = Vec::new();
+
+    for i in 0..unsafe { endpoints.GetCount()? } {
+        let endpoint = unsafe { endpoints.Item(i)? };
+
+        // e.g. built-in speakers, or an endpoint which was just removed
+        let Some(mac_address) = bluetooth_address(&endpoint) else {
+            continue;
+        };
+
+        if is_playing(&endpoint).unwrap_or(false) {
+            playing.push(mac_address);
+        }
+    }
+
+    Ok(playing)
+}
+
+/// The address of the bluetooth device an audio endpoint plays on, if it's one
+fn bluetooth_address(endpoint: &IMMDevice) -> Option<MacAddress> {
+    // SAFETY: the endpoint is a valid device, and the returned string is freed once copied
+    let device_id = unsafe {
+        let topology: IDeviceTopology = endpoint.Activate(CLSCTX_ALL, None).ok()?;
+        let connector = topology.GetConnector(0).ok()?;
+
+        let id = connector.GetDeviceIdConnectedTo().ok()?;
+        let device_id = id.to_string();
+
+        CoTaskMemFree(Some(id.0 as _));
+
+        device_id.ok()?
+    };
+
+    address_in_device_id(&device_id)
+}
+
+/// Finds the address in the id of a bluetooth audio driver's device,
+/// e.g. `\\?\BTHENUM#{0000110b-...}_VID&...#8&1c1f4e2a&0&A0E9DB123456_C00000000#{...}`
+fn address_in_device_id(device_id: &str) -> Option<MacAddress> {
+    let device_id = device_id.to_ascii_uppercase();
+
+    if !device_id.contains("BTHENUM") && !device_id.contains("BTHHFENUM") {
+        return None;
+    }
+
+    let end = device_id.find("_C00000000")?;
+    let address = device_id.get(end.checked_sub(12)?..end)?;
+
+    MacAddress::parse(address).ok()
+}
+
+fn is_playing(endpoint: &IMMDevice) -> windows::core::Result<bool> {
+    // SAFETY: the endpoint is a valid device
+    unsafe {
+        let manager: IAudioSessionManager2 = endpoint.Activate(CLSCTX_ALL, None)?;
+        let sessions = manager.GetSessionEnumerator()?;
+
+        for i in 0..sessions.GetCount()? {
+            let session = sessions.GetSession(i)?;
+
+            // an active session may still be playing silence, e.g. a paused video
+            if session.GetState()? == AudioSessionStateActive
+                && session.cast::<IAudioMeterInformation>()?.GetPeakValue()? > 0.0
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,251 @@
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc};
+use std::task::{Context, Poll, Waker};
+use tokio::task::{self, JoinHandle};
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DiscoverOptions, MacAddress};
+
+/// A blocking backend call running on tokio's blocking thread pool, which resolves to the call's result.
+///
+/// The call starts as soon as the task is created, whether or not it's awaited. Dropping the task detaches
+/// it: the call still runs to completion, but its result is discarded.
+#[must_use = "dropping a task discards its result"]
+#[derive(Debug)]
+pub struct BackendTask<T> {
+    handle: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> BackendTask<T> {
+    /// Starts `f` on the blocking thread pool; must be called from within a tokio runtime.
+    pub fn spawn(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self { handle: task::spawn_blocking(f) }
+    }
+}
+
+impl<T> BackendTask<T> {
+    /// Whether the call has completed, in which case awaiting the task won't block.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Takes the call's result without blocking, if it has completed; the task can't be awaited or taken from again after.
+    pub fn try_take(&mut self) -> Option<T> {
+        if !self.is_finished() {
+            return None;
+        }
+
+        match Pin::new(self).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(res) => Some(res),
+            Poll::Pending => None,
+        }
+    }
+}
+
+impl<T> Future for BackendTask<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Ready(Ok(res)) => Poll::Ready(res),
+            Poll::Ready(Err(err)) => match err.try_into_panic() {
+                Ok(payload) => panic::resume_unwind(payload),
+                // blocking tasks are only cancelled when the runtime shuts down, so there's no result to wait for
+                Err(_) => panic!("backend task was cancelled by its runtime shutting down"),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A [`BluetoothBackend::search_devices`] call running on the blocking thread pool, which resolves to whether the search succeeded.
+///
+/// Devices can be taken as they're found with [`found`](Self::found), including after the search completes.
+#[must_use = "dropping a task discards its result"]
+#[derive(Debug)]
+pub struct SearchTask {
+    task: BackendTask<Result<(), BluetoothError>>,
+    found: mpsc::Receiver<BluetoothDevice>,
+}
+
+impl SearchTask {
+    /// Starts `search` on the blocking thread pool, handing it a callback for each device found.
+    pub fn spawn(search: impl FnOnce(&mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> + Send + 'static) -> Self {
+        let (tx, found) = mpsc::channel();
+
+        Self {
+            // the receiver being dropped only means no one wants the devices anymore
+            task: BackendTask::spawn(move || search(&mut |device| { let _ = tx.send(device); })),
+            found,
+        }
+    }
+
+    /// Whether the search has completed, after which every device it found can be taken.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Takes the devices found since the last call, without blocking
+    pub fn found(&mut self) -> impl Iterator<Item = BluetoothDevice> + '_ {
+        self.found.try_iter()
+    }
+
+    /// Takes whether the search succeeded without blocking, if it has completed; see [`BackendTask::try_take`].
+    pub fn try_take(&mut self) -> Option<Result<(), BluetoothError>> {
+        self.task.try_take()
+    }
+}
+
+impl Future for SearchTask {
+    type Output = Result<(), BluetoothError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task).poll(cx)
+    }
+}
+
+/// Async variants of the [`BluetoothBackend`] operations, for backends shared through an [`Arc`].
+pub trait AsyncBluetoothBackend {
+    fn discover_devices_async(&self) -> BackendTask<Result<Vec<BluetoothDevice>, BluetoothError>>;
+
+    fn discover_devices_with_async(&self, options: DiscoverOptions) -> BackendTask<Result<Vec<BluetoothDevice>, BluetoothError>>;
+
+    fn search_devices_async(&self, options: DiscoverOptions) -> SearchTask;
+
+    fn get_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<BluetoothDevice, BluetoothError>>;
+
+    fn connect_to_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>>;
+
+    fn disconnect_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>>;
+
+    fn remove_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>>;
+
+    fn pair_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>>;
+}
+
+impl<B: BluetoothBackend + ?Sized + 'static> AsyncBluetoothBackend for Arc<B> {
+    fn discover_devices_async(&self) -> BackendTask<Result<Vec<BluetoothDevice>, BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.discover_devices())
+    }
+
+    fn discover_devices_with_async(&self, options: DiscoverOptions) -> BackendTask<Result<Vec<BluetoothDevice>, BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.discover_devices_with(&options))
+    }
+
+    fn search_devices_async(&self, options: DiscoverOptions) -> SearchTask {
+        let backend = self.clone();
+        SearchTask::spawn(move |found| backend.search_devices(&options, found))
+    }
+
+    fn get_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<BluetoothDevice, BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.get_device(mac_address))
+    }
+
+    fn connect_to_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.connect_to_device(mac_address))
+    }
+
+    fn disconnect_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.disconnect_device(mac_address))
+    }
+
+    fn remove_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.remove_device(mac_address))
+    }
+
+    fn pair_device_async(&self, mac_address: MacAddress) -> BackendTask<Result<(), BluetoothError>> {
+        let backend = self.clone();
+        BackendTask::spawn(move || backend.pair_device(mac_address))
+    }
+}
+
+#[cfg(windows)]
+pub fn discover_devices_async(radio: Option<crate::Radio>, options: DiscoverOptions) -> BackendTask<Result<Vec<BluetoothDevice>, BluetoothError>> {
+    BackendTask::spawn(move || crate::discover_devices(radio.as_ref(), &options))
+}
+
+#[cfg(windows)]
+pub fn search_devices_async(radio: Option<crate::Radio>, options: DiscoverOptions) -> SearchTask {
+    SearchTask::spawn(move |found| {
+        for device in crate::DeviceSearch::new(radio.as_ref(), &options)? {
+            found(device?);
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(windows)]
+pub fn get_device_async(mac_address: MacAddress, radio: Option<crate::Radio>) -> BackendTask<Result<BluetoothDevice, BluetoothError>> {
+    BackendTask::spawn(move || crate::get_device(mac_address, radio.as_ref()))
+}
+
+#[cfg(windows)]
+pub fn connect_to_device_os_async(mac_address: MacAddress, radio: Option<crate::Radio>, agent: Option<Arc<dyn crate::PairingAgent>>) -> BackendTask<Result<(), BluetoothError>> {
+    BackendTask::spawn(move || crate::connect_to_device_os(mac_address, radio.as_ref(), agent.as_deref()))
+}
+
+#[cfg(windows)]
+pub fn pair_device_os_async(mac_address: MacAddress, radio: Option<crate::Radio>, agent: Option<Arc<dyn crate::PairingAgent>>) -> BackendTask<Result<(), BluetoothError>> {
+    BackendTask::spawn(move || crate::pair_device_os(mac_address, radio.as_ref(), agent.as_deref()))
+}
+
+#[cfg(windows)]
+pub fn disconnect_device_async(mac_address: MacAddress, radio: Option<crate::Radio>) -> BackendTask<Result<(), BluetoothError>> {
+    BackendTask::spawn(move || crate::disconnect_device(mac_address, radio.as_ref()))
+}
+
+#[cfg(windows)]
+pub fn remove_device_async(mac_address: MacAddress, radio: Option<crate::Radio>) -> BackendTask<Result<(), BluetoothError>> {
+    BackendTask::spawn(move || crate::remove_device(mac_address, radio.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tokio::runtime::Builder;
+    use super::BackendTask;
+
+    #[test]
+    fn try_take_only_returns_a_finished_result() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let _guard = runtime.enter();
+        let (tx, rx) = mpsc::channel::<()>();
+
+        let mut task = BackendTask::spawn(move || rx.recv().map(|()| 5));
+
+        assert_eq!(task.try_take(), None);
+
+        tx.send(()).unwrap();
+
+        while !task.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(task.try_take(), Some(Ok(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "cancelled")]
+    fn cancelled_task_panics_instead_of_pending_forever() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let handle = runtime.handle().clone();
+
+        runtime.shutdown_background();
+
+        // calls spawned on a runtime which has shut down are cancelled right away
+        let task = {
+            let _guard = handle.enter();
+            BackendTask::spawn(|| ())
+        };
+
+        Builder::new_current_thread().build().unwrap().block_on(task);
+    }
+}
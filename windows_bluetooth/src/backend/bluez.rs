@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DeviceClass, DiscoverOptions, ErrorKind, Failure, MacAddress, Operation};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const OBJECT_MANAGER_INTERFACE: &str = "org.freedesktop.DBus.ObjectManager";
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+
+type Properties = HashMap<String, OwnedValue>;
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, Properties>>;
+
+/// Backend over BlueZ's D-Bus api.
+pub struct BluezBackend {
+    connection: Connection,
+    service: String,
+}
+
+struct DeviceObject {
+    path: OwnedObjectPath,
+    adapter: OwnedObjectPath,
+    device: BluetoothDevice,
+}
+
+impl BluezBackend {
+    /// Connects to BlueZ over the system bus.
+    pub fn new() -> zbus::Result<Self> {
+        Ok(Self::with_connection(Connection::system()?, BLUEZ_SERVICE))
+    }
+
+    /// Talks to the BlueZ service owning `service` on an existing connection, e.g. a mock service on a private session bus.
+    pub fn with_connection(connection: Connection, service: impl Into<String>) -> Self {
+        Self {
+            connection,
+            service: service.into(),
+        }
+    }
+
+    fn proxy<'a>(&'a self, path: &'a OwnedObjectPath, interface: &'static str) -> zbus::Result<Proxy<'a>> {
+        Proxy::new(&self.connection, self.service.as_str(), path.as_ref(), interface)
+    }
+
+    fn managed_objects(&self) -> zbus::Result<ManagedObjects> {
+        Proxy::new(&self.connection, self.service.as_str(), "/", OBJECT_MANAGER_INTERFACE)?
+            .call("GetManagedObjects", &())
+    }
+
+    fn adapter_paths(&self) -> zbus::Result<Vec<OwnedObjectPath>> {
+        Ok(
+            self.managed_objects()?
+                .into_iter()
+                .filter(|(_, interfaces)| interfaces.contains_key(ADAPTER_INTERFACE))
+                .map(|(path, _)| path)
+                .collect()
+        )
+    }
+
+    /// Has every adapter search for devices in range for `duration`, which adds the devices it finds to the managed objects.
+    fn inquiry(&self, duration: Duration) -> zbus::Result<()> {
+        let paths = self.adapter_paths()?;
+        let adapters = paths.iter()
+            .map(|path| self.proxy(path, ADAPTER_INTERFACE))
+            .collect::<zbus::Result<Vec<_>>>()?;
+
+        for adapter in &adapters {
+            match adapter.call_method("StartDiscovery", &()) {
+                Ok(_) => {}
+                Err(err) if error_name(&err) == Some("org.bluez.Error.InProgress") => {} // another client is already scanning
+                Err(err) => return Err(err),
+            }
+        }
+
+        thread::sleep(duration);
+
+        for adapter in &adapters {
+            // the devices found so far are kept either way, and BlueZ stops discovery by itself once no client wants it
+            let _ = adapter.call_method("StopDiscovery", &());
+        }
+
+        Ok(())
+    }
+
+    fn device_objects(&self) -> zbus::Result<Vec<DeviceObject>> {
+        Ok(
+            self.managed_objects()?
+                .into_iter()
+                .filter_map(|(path, mut interfaces)| {
+                    let properties = interfaces.remove(DEVICE_INTERFACE)?;
+
+                    Some(DeviceObject {
+                        path,
+                        adapter: property(&properties, "Adapter")?,
+                        device: bluetooth_device_from_properties(&properties)?,
+                    })
+                })
+                .collect()
+        )
+    }
+
+    fn find_device(&self, mac_address: MacAddress) -> zbus::Result<Option<DeviceObject>> {
+        Ok(
+            self.device_objects()?
+                .into_iter()
+                .find(|object| object.device.mac_address == mac_address)
+        )
+    }
+}
+
+impl BluezBackend {
+    fn discover(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, Failure> {
+        if let Some(duration) = options.inquiry_duration() {
+            self.inquiry(duration)?;
+        }
+
+        Ok(
+            self.device_objects()?
+                .into_iter()
+                .map(|object| object.device)
+                .filter(|device| options.matches(device))
+                .collect()
+        )
+    }
+
+    fn get(&self, mac_address: MacAddress) -> Result<BluetoothDevice, Failure> {
+        let object = self.find_device(mac_address)?.ok_or(Failure::new(ErrorKind::InvalidMacAddress))?;
+
+        Ok(object.device)
+    }
+
+    fn connect(&self, mac_address: MacAddress) -> Result<(), Failure> {
+        let object = self.find_device(mac_address)?.ok_or(Failure::new(ErrorKind::InvalidMacAddress))?;
+
+        if object.device.connected {
+            return Ok(());
+        }
+
+        let device = self.proxy(&object.path, DEVICE_INTERFACE)?;
+
+        if !object.device.authenticated {
+            match device.call_method("Pair", &()) {
+                Ok(_) => {}
+                Err(err) if error_name(&err) == Some("org.bluez.Error.AlreadyExists") => {} // paired in the meantime
+                Err(err) => return Err(err.into()),
+            }
+
+            // trusting the device lets it reconnect without asking, like a remembered device on Windows
+            device.set_property("Trusted", true).map_err(zbus::Error::from)?;
+        }
+
+        match device.call_method("Connect", &()) {
+            Ok(_) => Ok(()),
+            Err(err) if error_name(&err) == Some("org.bluez.Error.AlreadyConnected") => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn disconnect(&self, mac_address: MacAddress) -> Result<(), Failure> {
+        let object = self.find_device(mac_address)?.ok_or(Failure::new(ErrorKind::InvalidMacAddress))?;
+
+        match self.proxy(&object.path, DEVICE_INTERFACE)?.call_method("Disconnect", &()) {
+            Ok(_) => Ok(()),
+            Err(err) if error_name(&err) == Some("org.bluez.Error.NotConnected") => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn remove(&self, mac_address: MacAddress) -> Result<(), Failure> {
+        let object = self.find_device(mac_address)?.ok_or(Failure::new(ErrorKind::InvalidMacAddress))?;
+
+        if !object.device.remembered {
+            return Err(Failure::new(ErrorKind::NotRemembered));
+        }
+
+        self.proxy(&object.adapter, ADAPTER_INTERFACE)?
+            .call_method("RemoveDevice", &(&object.path,))?;
+
+        Ok(())
+    }
+}
+
+impl BluetoothBackend for BluezBackend {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        self.discover(options).map_err(|err| err.during(Operation::Discover, None))
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        self.get(mac_address).map_err(|err| err.during(Operation::Get, Some(mac_address)))
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.connect(mac_address).map_err(|err| err.during(Operation::Connect, Some(mac_address)))
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.disconnect(mac_address).map_err(|err| err.during(Operation::Disconnect, Some(mac_address)))
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.remove(mac_address).map_err(|err| err.during(Operation::Remove, Some(mac_address)))
+    }
+}
+
+fn property<T>(properties: &Properties, name: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    properties.get(name)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
+
+fn bluetooth_device_from_properties(properties: &Properties) -> Option<BluetoothDevice> {
+    let mac_address = property::<String>(properties, "Address")?.parse().ok()?;
+    let flag = |name| property::<bool>(properties, name).unwrap_or(false);
+
+    // every paired device is remembered, but trusted devices can be remembered without being paired
+    let authenticated = flag("Paired") || flag("Bonded");
+    let remembered = authenticated || flag("Trusted");
+
+    Some(BluetoothDevice {
+        mac_address,
+        name: property(properties, "Name"),
+        class: DeviceClass::from_raw(property(properties, "Class").unwrap_or(0)),
+        // same as on Windows, where a device isn't treated as connected unless it's also remembered
+        connected: flag("Connected") && remembered,
+        remembered,
+        authenticated,
+        // BlueZ doesn't keep track of when devices were last seen or used
+        last_seen: None,
+        last_used: None,
+        services: property::<Vec<String>>(properties, "UUIDs")
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|uuid| uuid.parse().ok())
+            .collect(),
+    })
+}
+
+fn error_name(err: &zbus::Error) -> Option<&str> {
+    match err {
+        zbus::Error::MethodError(name, _, _) => Some(name.as_str()),
+        zbus::Error::FDO(err) => match **err {
+            zbus::fdo::Error::ServiceUnknown(_) => Some("org.freedesktop.DBus.Error.ServiceUnknown"),
+            zbus::fdo::Error::NameHasNoOwner(_) => Some("org.freedesktop.DBus.Error.NameHasNoOwner"),
+            zbus::fdo::Error::UnknownObject(_) => Some("org.freedesktop.DBus.Error.UnknownObject"),
+            zbus::fdo::Error::AccessDenied(_) => Some("org.freedesktop.DBus.Error.AccessDenied"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn classify(err: &zbus::Error) -> ErrorKind {
+    match error_name(err) {
+        // bluetoothd isn't running or the adapter is off or missing
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+        | Some("org.freedesktop.DBus.Error.NameHasNoOwner")
+        | Some("org.bluez.Error.NotReady")
+        | Some("org.bluez.Error.NotAvailable") => ErrorKind::BluetoothUnavailable,
+        Some("org.freedesktop.DBus.Error.UnknownObject")
+        | Some("org.bluez.Error.DoesNotExist")
+        | Some("org.bluez.Error.InvalidArguments") => ErrorKind::InvalidMacAddress,
+        Some("org.bluez.Error.AuthenticationCanceled")
+        | Some("org.bluez.Error.AuthenticationFailed")
+        | Some("org.bluez.Error.AuthenticationRejected")
+        | Some("org.bluez.Error.AuthenticationTimeout")
+        | Some("org.bluez.Error.Canceled") => ErrorKind::Cancelled,
+        Some("org.freedesktop.DBus.Error.AccessDenied")
+        | Some("org.bluez.Error.NotAuthorized")
+        | Some("org.bluez.Error.NotPermitted") => ErrorKind::AccessDenied,
+        _ if matches!(err, zbus::Error::InputOutput(_)) => ErrorKind::BluetoothUnavailable,
+        _ => ErrorKind::Unhandled,
+    }
+}
+
+impl From<zbus::Error> for Failure {
+    fn from(err: zbus::Error) -> Self {
+        Self {
+            kind: classify(&err),
+            // D-Bus errors are identified by name, which is part of the source's message
+            code: None,
+            source: Some(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use zbus::blocking::Connection;
+    use zbus::blocking::connection::Builder;
+    use zbus::fdo::ObjectManager;
+    use zbus::zvariant::OwnedObjectPath;
+    use zbus::{interface, DBusError, Guid, ObjectServer};
+    use crate::{BluetoothBackend, DeviceClass, DiscoverOptions, ErrorKind, MacAddress, Operation};
+    use crate::backend::test_devices::{HEADPHONES, SPEAKER};
+    use super::{BluezBackend, BLUEZ_SERVICE};
+
+    const ADAPTER_PATH: &str = "/org/bluez/hci0";
+
+    #[derive(Debug, DBusError)]
+    #[zbus(prefix = "org.bluez.Error")]
+    enum BluezError {
+        #[zbus(error)]
+        ZBus(zbus::Error),
+        AlreadyConnected(String),
+        NotConnected(String),
+        DoesNotExist(String),
+        AuthenticationCanceled(String),
+        NotReady(String),
+    }
+
+    struct MockAdapter {
+        /// Devices which are added once discovery starts
+        nearby: Vec<MockDevice>,
+    }
+
+    #[interface(name = "org.bluez.Adapter1")]
+    impl MockAdapter {
+        async fn start_discovery(&mut self, #[zbus(object_server)] server: &ObjectServer) -> Result<(), BluezError> {
+            for device in self.nearby.drain(..) {
+                server.at(device.path(), device).await?;
+            }
+
+            Ok(())
+        }
+
+        fn stop_discovery(&self) {}
+
+        async fn remove_device(&self, device: OwnedObjectPath, #[zbus(object_server)] server: &ObjectServer) -> Result<(), BluezError> {
+            match server.remove::<MockDevice, _>(&device).await? {
+                true => Ok(()),
+                false => Err(BluezError::DoesNotExist(device.to_string())),
+            }
+        }
+    }
+
+    struct MockDevice {
+        address: MacAddress,
+        name: String,
+        class: u32,
+        uuids: Vec<String>,
+        paired: bool,
+        trusted: bool,
+        connected: bool,
+        /// Fails every connect with this error
+        connect_error: Option<fn(String) -> BluezError>,
+    }
+
+    impl MockDevice {
+        fn new(address: MacAddress, name: &str) -> Self {
+            Self {
+                address,
+                name: name.to_string(),
+                class: 0,
+                uuids: Vec::new(),
+                paired: false,
+                trusted: false,
+                connected: false,
+                connect_error: None,
+            }
+        }
+
+        fn already_paired(mut self) -> Self {
+            self.paired = true;
+            self.trusted = true;
+            self
+        }
+
+        fn path(&self) -> String {
+            format!("{ADAPTER_PATH}/dev_{}", self.address.to_string().replace(':', "_"))
+        }
+    }
+
+    #[interface(name = "org.bluez.Device1")]
+    impl MockDevice {
+        fn pair(&mut self) {
+            self.paired = true;
+        }
+
+        fn connect(&mut self) -> Result<(), BluezError> {
+            if let Some(err) = self.connect_error {
+                return Err(err("connect failed".to_string()));
+            }
+
+            if self.connected {
+                return Err(BluezError::AlreadyConnected("already connected".to_string()));
+            }
+
+            self.connected = true;
+
+            Ok(())
+        }
+
+        fn disconnect(&mut self) -> Result<(), BluezError> {
+            if !self.connected {
+                return Err(BluezError::NotConnected("not connected".to_string()));
+            }
+
+            self.connected = false;
+
+            Ok(())
+        }
+
+        #[zbus(property)]
+        fn address(&self) -> String {
+            self.address.to_string()
+        }
+
+        #[zbus(property)]
+        fn adapter(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(ADAPTER_PATH).unwrap()
+        }
+
+        #[zbus(property)]
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        #[zbus(property)]
+        fn class(&self) -> u32 {
+            self.class
+        }
+
+        #[zbus(property, name = "UUIDs")]
+        fn uuids(&self) -> Vec<String> {
+            self.uuids.clone()
+        }
+
+        #[zbus(property)]
+        fn paired(&self) -> bool {
+            self.paired
+        }
+
+        #[zbus(property)]
+        fn trusted(&self) -> bool {
+            self.trusted
+        }
+
+        #[zbus(property)]
+        fn set_trusted(&mut self, trusted: bool) {
+            self.trusted = trusted;
+        }
+
+        #[zbus(property)]
+        fn connected(&self) -> bool {
+            self.connected
+        }
+    }
+
+    /// A backend talking to a mock BlueZ over a peer-to-peer connection, along with the mock's end, which has to be kept alive
+    fn mock_bluez(devices: Vec<MockDevice>, nearby: Vec<MockDevice>) -> (BluezBackend, Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+
+        // both ends have to build at once, since they authenticate with each other
+        let server = thread::spawn(move || {
+            let mut builder = Builder::unix_stream(server)
+                .server(Guid::generate()).unwrap()
+                .p2p()
+                .serve_at("/", ObjectManager).unwrap()
+                .serve_at(ADAPTER_PATH, MockAdapter { nearby }).unwrap();
+
+            for device in devices {
+                builder = builder.serve_at(device.path(), device).unwrap();
+            }
+
+            builder.build().unwrap()
+        });
+
+        let client = Builder::unix_stream(client).p2p().build().unwrap();
+
+        (BluezBackend::with_connection(client, BLUEZ_SERVICE), server.join().unwrap())
+    }
+
+    #[test]
+    fn discover_maps_device_properties() {
+        let headphones = MockDevice {
+            class: 0x240404,
+            uuids: vec!["0000110b-0000-1000-8000-00805f9b34fb".to_string(), "not a uuid".to_string()],
+            connected: true,
+            ..MockDevice::new(HEADPHONES, "WH-1000XM4").already_paired()
+        };
+        // connected without being remembered, which isn't treated as connected, same as on Windows
+        let speaker = MockDevice { connected: true, ..MockDevice::new(SPEAKER, "Speaker") };
+
+        let (backend, _server) = mock_bluez(vec![headphones, speaker], Vec::new());
+
+        let mut devices = backend.discover_devices().unwrap();
+        devices.sort_by_key(|device| device.mac_address());
+
+        assert_eq!(devices.len(), 2);
+
+        assert_eq!(devices[0].mac_address(), HEADPHONES);
+        assert_eq!(devices[0].name(), Some("WH-1000XM4"));
+        assert_eq!(devices[0].class(), DeviceClass::from_raw(0x240404));
+        assert_eq!(devices[0].services().len(), 1);
+        assert!(devices[0].is_connected() && devices[0].is_remembered() && devices[0].is_authenticated());
+
+        assert_eq!(devices[1].mac_address(), SPEAKER);
+        assert!(!devices[1].is_connected() && !devices[1].is_remembered() && !devices[1].is_authenticated());
+
+        let unknown = backend.discover_devices_with(&DiscoverOptions::new().authenticated(false).remembered(false).connected(false)).unwrap();
+
+        assert_eq!(unknown.iter().map(|device| device.mac_address()).collect::<Vec<_>>(), [SPEAKER]);
+    }
+
+    #[test]
+    fn inquiry_finds_nearby_devices() {
+        let (backend, _server) = mock_bluez(Vec::new(), vec![MockDevice::new(SPEAKER, "Speaker")]);
+
+        assert!(backend.discover_devices().unwrap().is_empty());
+
+        let found = backend.discover_devices_with(&DiscoverOptions::new().inquiry(DiscoverOptions::INQUIRY_UNIT)).unwrap();
+
+        assert_eq!(found.iter().map(|device| device.mac_address()).collect::<Vec<_>>(), [SPEAKER]);
+    }
+
+    #[test]
+    fn connect_pairs_and_trusts_new_devices() {
+        let speaker = MockDevice::new(SPEAKER, "Speaker");
+        let path = speaker.path();
+        let (backend, server) = mock_bluez(vec![speaker], Vec::new());
+
+        backend.connect_to_device(SPEAKER).unwrap();
+
+        let speaker = backend.get_device(SPEAKER).unwrap();
+        assert!(speaker.is_connected() && speaker.is_remembered() && speaker.is_authenticated());
+        assert!(server.object_server().interface::<_, MockDevice>(path).unwrap().get().trusted);
+
+        // already connected
+        backend.connect_to_device(SPEAKER).unwrap();
+    }
+
+    #[test]
+    fn disconnect_keeps_the_pairing() {
+        let headphones = MockDevice { connected: true, ..MockDevice::new(HEADPHONES, "WH-1000XM4").already_paired() };
+        let (backend, _server) = mock_bluez(vec![headphones], Vec::new());
+
+        backend.disconnect_device(HEADPHONES).unwrap();
+
+        let headphones = backend.get_device(HEADPHONES).unwrap();
+        assert!(!headphones.is_connected() && headphones.is_remembered() && headphones.is_authenticated());
+
+        // BlueZ's NotConnected means there's nothing left to do
+        backend.disconnect_device(HEADPHONES).unwrap();
+
+        backend.connect_to_device(HEADPHONES).unwrap();
+        assert!(backend.get_device(HEADPHONES).unwrap().is_connected());
+    }
+
+    #[test]
+    fn remove_forgets_the_device() {
+        let headphones = MockDevice { connected: true, ..MockDevice::new(HEADPHONES, "WH-1000XM4").already_paired() };
+        let (backend, _server) = mock_bluez(vec![headphones, MockDevice::new(SPEAKER, "Speaker")], Vec::new());
+
+        backend.remove_device(HEADPHONES).unwrap();
+
+        let err = backend.get_device(HEADPHONES).unwrap_err();
+        assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::InvalidMacAddress, Operation::Get, Some(HEADPHONES)));
+
+        let err = backend.remove_device(HEADPHONES).unwrap_err();
+        assert_eq!((err.kind(), err.operation()), (ErrorKind::InvalidMacAddress, Operation::Remove));
+
+        let err = backend.remove_device(SPEAKER).unwrap_err();
+        assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::NotRemembered, Operation::Remove, Some(SPEAKER)));
+    }
+
+    #[test]
+    fn dbus_errors_are_classified() {
+        let cancelled = MockDevice { connect_error: Some(BluezError::AuthenticationCanceled), ..MockDevice::new(HEADPHONES, "WH-1000XM4") };
+        let not_ready = MockDevice { connect_error: Some(BluezError::NotReady), ..MockDevice::new(SPEAKER, "Speaker").already_paired() };
+
+        let (backend, _server) = mock_bluez(vec![cancelled, not_ready], Vec::new());
+
+        let err = backend.connect_to_device(HEADPHONES).unwrap_err();
+        assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::Cancelled, Operation::Connect, Some(HEADPHONES)));
+
+        let err = backend.connect_to_device(SPEAKER).unwrap_err();
+        assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::BluetoothUnavailable, Operation::Connect, Some(SPEAKER)));
+    }
+}
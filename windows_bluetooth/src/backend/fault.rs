@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, Operation, PairingAgent};
+
+/// A failure to inject into a backend call.
+///
+/// Faults which don't apply to an operation, like [`Fault::Cancelled`] on a scan, are ignored.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Fault {
+    /// Fails with [`ErrorKind::BluetoothUnavailable`]; scans still run, and fail as if the adapter went away mid-scan
+    BluetoothUnavailable,
+    /// Fails a connect with [`ErrorKind::Cancelled`], as if the user dismissed the pairing prompt
+    Cancelled,
+    /// Fails a remove with [`ErrorKind::NotRemembered`]
+    NotRemembered,
+    /// Waits before making the call
+    Slow(Duration),
+    /// Never returns, blocking the calling thread forever
+    Hang,
+}
+
+/// Faults to inject in order, one entry per call of an operation; calls past the end of the schedule aren't faulted.
+#[derive(Debug, Clone, Default)]
+pub struct FaultSchedule {
+    discover: VecDeque<Option<Fault>>,
+    get: VecDeque<Option<Fault>>,
+    connect: VecDeque<Option<Fault>>,
+    disconnect: VecDeque<Option<Fault>>,
+    remove: VecDeque<Option<Fault>>,
+}
+
+impl FaultSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules the fault of the next call of `operation`, where `None` lets the call through untouched.
+    pub fn then(mut self, operation: Operation, fault: Option<Fault>) -> Self {
+        self.queue(operation).push_back(fault);
+        self
+    }
+
+    fn queue(&mut self, operation: Operation) -> &mut VecDeque<Option<Fault>> {
+        match operation {
+            Operation::Discover => &mut self.discover,
+            Operation::Get => &mut self.get,
+            Operation::Connect => &mut self.connect,
+            Operation::Disconnect => &mut self.disconnect,
+            Operation::Remove => &mut self.remove,
+        }
+    }
+}
+
+/// Probability of each fault for each operation, rolled on every call.
+#[derive(Debug, Clone, Default)]
+pub struct FaultRates {
+    rates: Vec<(Operation, Fault, f64)>,
+}
+
+impl FaultRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Injects `fault` into calls of `operation` with the given probability; rates of the same operation shouldn't sum past 1.
+    pub fn with(mut self, operation: Operation, fault: Fault, probability: f64) -> Self {
+        self.rates.push((operation, fault, probability));
+        self
+    }
+}
+
+#[derive(Debug)]
+enum FaultPlan {
+    Schedule(FaultSchedule),
+    Random(Box<StdRng>, FaultRates),
+}
+
+impl FaultPlan {
+    fn next(&mut self, operation: Operation) -> Option<Fault> {
+        match self {
+            FaultPlan::Schedule(schedule) => schedule.queue(operation).pop_front().flatten(),
+            FaultPlan::Random(rng, rates) => {
+                let roll = rng.r#gen::<f64>();
+                let mut cumulative = 0.0;
+
+                rates.rates.iter()
+                    .filter(|(op, _, _)| *op == operation)
+                    .find(|(_, _, probability)| {
+                        cumulative += probability;
+                        roll < cumulative
+                    })
+                    .map(|(_, fault, _)| *fault)
+            }
+        }
+    }
+}
+
+/// Wraps another backend and injects failures into its calls, following either a schedule or a seeded RNG.
+#[derive(Debug)]
+pub struct FaultInjectingBackend<B> {
+    inner: B,
+    plan: Mutex<FaultPlan>,
+}
+
+impl<B: BluetoothBackend> FaultInjectingBackend<B> {
+    pub fn with_schedule(inner: B, schedule: FaultSchedule) -> Self {
+        Self {
+            inner,
+            plan: Mutex::new(FaultPlan::Schedule(schedule)),
+        }
+    }
+
+    /// Injects faults at random, the same seed always giving the same faults for the same sequence of calls.
+    pub fn with_rng(inner: B, seed: u64, rates: FaultRates) -> Self {
+        Self {
+            inner,
+            plan: Mutex::new(FaultPlan::Random(Box::new(StdRng::seed_from_u64(seed)), rates)),
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn plan(&self) -> MutexGuard<'_, FaultPlan> {
+        self.plan.lock().expect("fault plan shouldn't be poisoned")
+    }
+
+    /// Picks the fault for this call, and applies the ones which only delay it
+    fn next_fault(&self, operation: Operation) -> Option<Fault> {
+        let fault = self.plan().next(operation);
+
+        match fault {
+            Some(Fault::Slow(delay)) => thread::sleep(delay),
+            Some(Fault::Hang) => loop {
+                thread::park();
+            },
+            _ => {}
+        }
+
+        fault
+    }
+}
+
+impl<B: BluetoothBackend> BluetoothBackend for FaultInjectingBackend<B> {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        match self.next_fault(Operation::Discover) {
+            Some(Fault::BluetoothUnavailable) => {
+                // scan for real, but lose the adapter before the scan completes
+                let _ = self.inner.discover_devices_with(options);
+
+                Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Discover))
+            }
+            _ => self.inner.discover_devices_with(options),
+        }
+    }
+
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        match self.next_fault(Operation::Discover) {
+            Some(Fault::BluetoothUnavailable) => {
+                // hand over what was found before the adapter was lost
+                let _ = self.inner.search_devices(options, found);
+
+                Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Discover))
+            }
+            _ => self.inner.search_devices(options, found),
+        }
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        match self.next_fault(Operation::Get) {
+            Some(Fault::BluetoothUnavailable) => Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Get).with_mac_address(mac_address)),
+            _ => self.inner.get_device(mac_address),
+        }
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        match self.next_fault(Operation::Connect) {
+            Some(Fault::BluetoothUnavailable) => Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Connect).with_mac_address(mac_address)),
+            Some(Fault::Cancelled) => Err(BluetoothError::new(ErrorKind::Cancelled, Operation::Connect).with_mac_address(mac_address)),
+            _ => self.inner.connect_to_device(mac_address),
+        }
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        match self.next_fault(Operation::Disconnect) {
+            Some(Fault::BluetoothUnavailable) => Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Disconnect).with_mac_address(mac_address)),
+            _ => self.inner.disconnect_device(mac_address),
+        }
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        match self.next_fault(Operation::Remove) {
+            Some(Fault::BluetoothUnavailable) => Err(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Remove).with_mac_address(mac_address)),
+            Some(Fault::NotRemembered) => Err(BluetoothError::new(ErrorKind::NotRemembered, Operation::Remove).with_mac_address(mac_address)),
+            _ => self.inner.remove_device(mac_address),
+        }
+    }
+
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        self.inner.set_pairing_agent(agent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::{BluetoothBackend, BluetoothError, ErrorKind, Operation};
+    use crate::backend::test_devices::{mock, HEADPHONES};
+    use super::{Fault, FaultInjectingBackend, FaultRates, FaultSchedule};
+
+    /// The kind of error each of a series of connects and removes failed with, if any
+    fn outcomes(backend: &impl BluetoothBackend) -> Vec<Option<ErrorKind>> {
+        (0..50)
+            .flat_map(|_| [backend.connect_to_device(HEADPHONES), backend.remove_device(HEADPHONES)])
+            .map(|res| res.err().map(|err| err.kind()))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_faults() {
+        let rates = FaultRates::new()
+            .with(Operation::Connect, Fault::Cancelled, 0.3)
+            .with(Operation::Connect, Fault::BluetoothUnavailable, 0.2)
+            .with(Operation::Remove, Fault::NotRemembered, 0.4);
+
+        let first = outcomes(&FaultInjectingBackend::with_rng(mock(), 7, rates.clone()));
+        let second = outcomes(&FaultInjectingBackend::with_rng(mock(), 7, rates.clone()));
+
+        assert_eq!(first, second);
+        assert!(first.contains(&Some(ErrorKind::Cancelled)));
+        assert!(first.contains(&Some(ErrorKind::BluetoothUnavailable)));
+        assert!(first.contains(&None));
+
+        let other_seed = outcomes(&FaultInjectingBackend::with_rng(mock(), 8, rates));
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn schedule_fires_in_order() {
+        let schedule = FaultSchedule::new()
+            .then(Operation::Connect, Some(Fault::Cancelled))
+            .then(Operation::Connect, None)
+            .then(Operation::Connect, Some(Fault::BluetoothUnavailable))
+            .then(Operation::Remove, Some(Fault::NotRemembered))
+            // doesn't apply to gets
+            .then(Operation::Get, Some(Fault::Cancelled));
+
+        let backend = FaultInjectingBackend::with_schedule(mock(), schedule);
+        let kind = |res: Result<(), BluetoothError>| res.err().map(|err| err.kind());
+
+        // the remove's fault isn't used up by the connects in between
+        assert_eq!(kind(backend.connect_to_device(HEADPHONES)), Some(ErrorKind::Cancelled));
+        assert_eq!(kind(backend.connect_to_device(HEADPHONES)), None);
+        assert_eq!(kind(backend.connect_to_device(HEADPHONES)), Some(ErrorKind::BluetoothUnavailable));
+        assert_eq!(kind(backend.connect_to_device(HEADPHONES)), None, "calls past the schedule shouldn't be faulted");
+
+        assert_eq!(kind(backend.remove_device(HEADPHONES)), Some(ErrorKind::NotRemembered));
+        assert_eq!(kind(backend.remove_device(HEADPHONES)), None);
+
+        backend.get_device(HEADPHONES).unwrap();
+
+        let device = backend.into_inner().get_device(HEADPHONES).unwrap();
+        assert!(!device.is_remembered(), "only the unfaulted remove should have reached the inner backend");
+    }
+
+    #[test]
+    fn slow_fault_delays_the_call() {
+        let delay = Duration::from_millis(50);
+        let backend = FaultInjectingBackend::with_schedule(mock(), FaultSchedule::new().then(Operation::Get, Some(Fault::Slow(delay))));
+
+        let start = Instant::now();
+        backend.get_device(HEADPHONES).unwrap();
+
+        assert!(start.elapsed() >= delay);
+    }
+}
@@ -0,0 +1,631 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use chrono::{Local, NaiveDateTime};
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DeviceClass, DiscoverOptions, ErrorKind, MacAddress, Operation, PairingAgent, ServiceUuid};
+use crate::pairing::AgentSlot;
+
+/// Builder for a device known to a [`MockBackend`].
+#[derive(Debug, Clone)]
+pub struct MockDevice(BluetoothDevice);
+
+impl MockDevice {
+    pub fn new(mac_address: MacAddress) -> Self {
+        Self(BluetoothDevice {
+            mac_address,
+            name: None,
+            class: DeviceClass::default(),
+            connected: false,
+            remembered: false,
+            authenticated: false,
+            last_seen: None,
+            last_used: None,
+            services: Vec::new(),
+        })
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.0.name = Some(name.into());
+        self
+    }
+
+    pub fn class(mut self, class: impl Into<DeviceClass>) -> Self {
+        self.0.class = class.into();
+        self
+    }
+
+    pub fn connected(mut self, connected: bool) -> Self {
+        self.0.connected = connected;
+        self
+    }
+
+    pub fn remembered(mut self, remembered: bool) -> Self {
+        self.0.remembered = remembered;
+        self
+    }
+
+    pub fn authenticated(mut self, authenticated: bool) -> Self {
+        self.0.authenticated = authenticated;
+        self
+    }
+
+    pub fn last_seen(mut self, last_seen: NaiveDateTime) -> Self {
+        self.0.last_seen = Some(last_seen);
+        self
+    }
+
+    pub fn last_used(mut self, last_used: NaiveDateTime) -> Self {
+        self.0.last_used = Some(last_used);
+        self
+    }
+
+    pub fn service(mut self, uuid: impl Into<ServiceUuid>) -> Self {
+        self.0.services.push(uuid.into());
+        self
+    }
+
+    pub fn build(self) -> BluetoothDevice {
+        self.0
+    }
+}
+
+impl From<MockDevice> for BluetoothDevice {
+    fn from(device: MockDevice) -> Self {
+        device.build()
+    }
+}
+
+/// How a device asks a [`MockBackend`]'s [`PairingAgent`] to pair with it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MockPairing {
+    /// Legacy pairing, which succeeds if the agent gives this PIN
+    Pin(String),
+    /// Passkey entry, which succeeds if the agent gives this passkey, as if the device displayed it
+    EnterPasskey(u32),
+    /// Numeric comparison, which succeeds if the agent confirms this passkey
+    ConfirmPasskey(u32),
+    /// The device has a keyboard, so the agent displays this passkey to type on it, which always succeeds
+    DisplayPasskey(u32),
+}
+
+impl MockPairing {
+    /// Whether `agent` paired with the device
+    fn answer(&self, mac_address: MacAddress, agent: &dyn PairingAgent) -> bool {
+        match self {
+            MockPairing::Pin(pin) => agent.request_pin(mac_address).as_ref() == Some(pin),
+            MockPairing::EnterPasskey(passkey) => agent.request_passkey(mac_address) == Some(*passkey),
+            MockPairing::ConfirmPasskey(passkey) => agent.confirm_passkey(mac_address, *passkey),
+            MockPairing::DisplayPasskey(passkey) => {
+                agent.display_passkey(mac_address, *passkey);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    devices: Vec<BluetoothDevice>,
+    nearby: Vec<BluetoothDevice>,
+    pairings: HashMap<MacAddress, MockPairing>,
+    discover_latency: Duration,
+    get_latency: Duration,
+    connect_latency: Duration,
+    disconnect_latency: Duration,
+    remove_latency: Duration,
+    discover_failures: VecDeque<BluetoothError>,
+    get_failures: VecDeque<BluetoothError>,
+    connect_failures: VecDeque<BluetoothError>,
+    disconnect_failures: VecDeque<BluetoothError>,
+    remove_failures: VecDeque<BluetoothError>,
+}
+
+/// In-memory backend with scriptable devices, latencies and failures.
+///
+/// Connecting marks a device as connected, remembered and authenticated, disconnecting only clears connected,
+/// and removing clears all three, similar to how the Win32 backend behaves. Scripted failures are taken in the order they were queued,
+/// one per call to the matching operation as it starts, and returned instead of the simulated behavior once the call's latency has passed.
+///
+/// Nearby devices are in range but unknown to the backend, so only a scan with an inquiry finds them,
+/// after which they're known like any other device.
+///
+/// Devices with a scripted [`MockPairing`] ask the pairing agent when connecting while they aren't paired,
+/// and fail with [`ErrorKind::Cancelled`] if it rejects them. Without an agent, they pair silently like any other device.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    state: Mutex<MockState>,
+    agent: AgentSlot,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_devices(devices: impl IntoIterator<Item = MockDevice>) -> Self {
+        let backend = Self::new();
+
+        for device in devices {
+            backend.add_device(device);
+        }
+
+        backend
+    }
+
+    /// Adds a device, replacing any existing device with the same MAC address.
+    pub fn add_device(&self, device: impl Into<BluetoothDevice>) {
+        let device = device.into();
+        let mut state = self.state();
+
+        state.devices.retain(|bd| bd.mac_address != device.mac_address);
+        state.devices.push(device);
+    }
+
+    /// Adds a device that's only found by a scan with an inquiry.
+    pub fn add_nearby_device(&self, device: impl Into<BluetoothDevice>) {
+        let device = device.into();
+        let mut state = self.state();
+
+        state.nearby.retain(|bd| bd.mac_address != device.mac_address);
+        state.nearby.push(device);
+    }
+
+    pub fn forget_device(&self, mac_address: MacAddress) -> Option<BluetoothDevice> {
+        let mut state = self.state();
+        let index = state.devices.iter().position(|bd| bd.mac_address == mac_address)?;
+
+        Some(state.devices.remove(index))
+    }
+
+    pub fn device(&self, mac_address: MacAddress) -> Option<BluetoothDevice> {
+        self.state().devices.iter().find(|bd| bd.mac_address == mac_address).cloned()
+    }
+
+    pub fn devices(&self) -> Vec<BluetoothDevice> {
+        self.state().devices.clone()
+    }
+
+    /// Changes a device in place, returning false if no device has the given MAC address.
+    pub fn update_device(&self, mac_address: MacAddress, f: impl FnOnce(&mut BluetoothDevice)) -> bool {
+        match self.state().devices.iter_mut().find(|bd| bd.mac_address == mac_address) {
+            Some(device) => {
+                f(device);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes a device ask the pairing agent to pair with it, replacing any earlier pairing of the device.
+    pub fn set_pairing(&self, mac_address: MacAddress, pairing: MockPairing) {
+        self.state().pairings.insert(mac_address, pairing);
+    }
+
+    pub fn set_latency(&self, operation: Operation, latency: Duration) {
+        let mut state = self.state();
+
+        match operation {
+            Operation::Discover => state.discover_latency = latency,
+            Operation::Get => state.get_latency = latency,
+            Operation::Connect => state.connect_latency = latency,
+            Operation::Disconnect => state.disconnect_latency = latency,
+            Operation::Remove => state.remove_latency = latency,
+        }
+    }
+
+    /// Fails the next call of the error's [`operation`](BluetoothError::operation) with it.
+    pub fn fail_next(&self, err: BluetoothError) {
+        let mut state = self.state();
+
+        match err.operation() {
+            Operation::Discover => state.discover_failures.push_back(err),
+            Operation::Get => state.get_failures.push_back(err),
+            Operation::Connect => state.connect_failures.push_back(err),
+            Operation::Disconnect => state.disconnect_failures.push_back(err),
+            Operation::Remove => state.remove_failures.push_back(err),
+        }
+    }
+
+    fn scan(&self, options: &DiscoverOptions) -> Vec<BluetoothDevice> {
+        let mut state = self.state();
+
+        if options.inquiry_duration().is_some() {
+            let found = std::mem::take(&mut state.nearby);
+
+            for device in found {
+                state.devices.retain(|bd| bd.mac_address != device.mac_address);
+                state.devices.push(device);
+            }
+        }
+
+        let now = Local::now().naive_local();
+
+        for device in &mut state.devices {
+            device.last_seen = Some(now);
+        }
+
+        state.devices.iter().filter(|bd| options.matches(bd)).cloned().collect()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock state shouldn't be poisoned")
+    }
+
+    /// Takes the next scripted failure of the operation, if there is one
+    fn take_failure(&self, operation: Operation) -> Option<BluetoothError> {
+        let mut state = self.state();
+
+        match operation {
+            Operation::Discover => state.discover_failures.pop_front(),
+            Operation::Get => state.get_failures.pop_front(),
+            Operation::Connect => state.connect_failures.pop_front(),
+            Operation::Disconnect => state.disconnect_failures.pop_front(),
+            Operation::Remove => state.remove_failures.pop_front(),
+        }
+    }
+
+    /// Waits out the operation's latency, then returns its scripted failure if it had one
+    fn start(&self, operation: Operation) -> Result<(), BluetoothError> {
+        let failure = self.take_failure(operation);
+
+        self.wait(operation);
+
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn wait(&self, operation: Operation) {
+        let latency = {
+            let state = self.state();
+
+            match operation {
+                Operation::Discover => state.discover_latency,
+                Operation::Get => state.get_latency,
+                Operation::Connect => state.connect_latency,
+                Operation::Disconnect => state.disconnect_latency,
+                Operation::Remove => state.remove_latency,
+            }
+        };
+
+        // sleep without holding the lock, so the mock can be scripted while an operation is in flight
+        if !latency.is_zero() {
+            thread::sleep(latency);
+        }
+    }
+}
+
+impl BluetoothBackend for MockBackend {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        self.start(Operation::Discover)?;
+
+        Ok(self.scan(options))
+    }
+
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        if let Some(err) = self.take_failure(Operation::Discover) {
+            self.wait(Operation::Discover);
+
+            return Err(err);
+        }
+
+        let devices = self.scan(options);
+
+        // spread the latency over the devices, as if each took a while to find
+        let latency = self.state().discover_latency / (devices.len() as u32 + 1);
+
+        thread::sleep(latency);
+
+        for device in devices {
+            found(device);
+            thread::sleep(latency);
+        }
+
+        Ok(())
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        self.start(Operation::Get)?;
+
+        let state = self.state();
+
+        state.devices
+            .iter()
+            .find(|bd| bd.mac_address == mac_address)
+            .cloned()
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Get).with_mac_address(mac_address))
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.start(Operation::Connect)?;
+
+        let invalid = || BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Connect).with_mac_address(mac_address);
+
+        let pairing = {
+            let state = self.state();
+
+            let device = state.devices
+                .iter()
+                .find(|bd| bd.mac_address == mac_address)
+                .ok_or_else(invalid)?;
+
+            match device.authenticated {
+                true => None,
+                false => state.pairings.get(&mac_address).cloned(),
+            }
+        };
+
+        // ask without holding the lock, since the agent may wait on the user
+        if let (Some(pairing), Some(agent)) = (pairing, self.agent.get())
+            && !pairing.answer(mac_address, &*agent) {
+            return Err(BluetoothError::new(ErrorKind::Cancelled, Operation::Connect).with_mac_address(mac_address));
+        }
+
+        let mut state = self.state();
+
+        // the device may have been forgotten while pairing
+        let device = state.devices
+            .iter_mut()
+            .find(|bd| bd.mac_address == mac_address)
+            .ok_or_else(invalid)?;
+
+        device.connected = true;
+        device.remembered = true;
+        device.authenticated = true;
+        device.last_used = Some(Local::now().naive_local());
+
+        Ok(())
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.start(Operation::Disconnect)?;
+
+        let mut state = self.state();
+
+        let device = state.devices
+            .iter_mut()
+            .find(|bd| bd.mac_address == mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Disconnect).with_mac_address(mac_address))?;
+
+        device.connected = false;
+
+        Ok(())
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.start(Operation::Remove)?;
+
+        let mut state = self.state();
+
+        let device = state.devices
+            .iter_mut()
+            .find(|bd| bd.mac_address == mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Remove).with_mac_address(mac_address))?;
+
+        if !device.remembered {
+            return Err(BluetoothError::new(ErrorKind::NotRemembered, Operation::Remove).with_mac_address(mac_address));
+        }
+
+        device.connected = false;
+        device.remembered = false;
+        device.authenticated = false;
+
+        Ok(())
+    }
+
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        self.agent.set(agent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use crate::{BluetoothBackend, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, Operation, PairingAgent};
+    use crate::backend::test_devices::{mock, speaker, HEADPHONES, SPEAKER};
+    use super::{MockBackend, MockDevice, MockPairing};
+
+    fn flags(backend: &MockBackend, mac_address: MacAddress) -> (bool, bool, bool) {
+        let device = backend.device(mac_address).unwrap();
+
+        (device.is_connected(), device.is_remembered(), device.is_authenticated())
+    }
+
+    #[test]
+    fn failures_are_queued_per_operation() {
+        let backend = mock();
+
+        backend.fail_next(BluetoothError::new(ErrorKind::Cancelled, Operation::Connect));
+        backend.fail_next(BluetoothError::new(ErrorKind::AccessDenied, Operation::Connect));
+        backend.fail_next(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Remove));
+
+        // other operations aren't affected
+        backend.get_device(HEADPHONES).unwrap();
+        backend.disconnect_device(HEADPHONES).unwrap();
+
+        assert_eq!(backend.connect_to_device(HEADPHONES).unwrap_err().kind(), ErrorKind::Cancelled);
+        assert_eq!(backend.connect_to_device(HEADPHONES).unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert!(!backend.device(HEADPHONES).unwrap().is_connected(), "failed connects shouldn't change the device");
+
+        backend.connect_to_device(HEADPHONES).unwrap();
+
+        assert_eq!(backend.remove_device(HEADPHONES).unwrap_err().kind(), ErrorKind::BluetoothUnavailable);
+        backend.remove_device(HEADPHONES).unwrap();
+    }
+
+    #[test]
+    fn scans_fail_the_same_whether_searching_or_not() {
+        let backend = mock();
+
+        backend.fail_next(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Discover));
+        backend.fail_next(BluetoothError::new(ErrorKind::OutOfMemory, Operation::Discover));
+
+        assert_eq!(backend.discover_devices().unwrap_err().kind(), ErrorKind::BluetoothUnavailable);
+
+        let mut found = Vec::new();
+        let err = backend.search_devices(&DiscoverOptions::new(), &mut |device| found.push(device)).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+        assert!(found.is_empty());
+
+        backend.search_devices(&DiscoverOptions::new(), &mut |device| found.push(device)).unwrap();
+        assert_eq!(found.len(), 1);
+    }
+
+    #[test]
+    fn latency_comes_before_results_and_failures() {
+        let latency = Duration::from_millis(50);
+        let backend = mock();
+
+        backend.set_latency(Operation::Get, latency);
+        backend.set_latency(Operation::Discover, latency);
+
+        let start = Instant::now();
+        backend.get_device(HEADPHONES).unwrap();
+        assert!(start.elapsed() >= latency);
+
+        backend.fail_next(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Get));
+
+        let start = Instant::now();
+        backend.get_device(HEADPHONES).unwrap_err();
+        assert!(start.elapsed() >= latency);
+
+        for search in [false, true] {
+            backend.fail_next(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Discover));
+
+            let start = Instant::now();
+
+            match search {
+                true => backend.search_devices(&DiscoverOptions::new(), &mut |_| {}).unwrap_err(),
+                false => backend.discover_devices().unwrap_err(),
+            };
+
+            assert!(start.elapsed() >= latency, "failing scans should wait out the latency too, searching: {search}");
+        }
+
+        let start = Instant::now();
+        backend.search_devices(&DiscoverOptions::new(), &mut |_| {}).unwrap();
+        assert!(start.elapsed() >= latency);
+
+        // operations without a latency don't wait
+        let start = Instant::now();
+        backend.connect_to_device(HEADPHONES).unwrap();
+        assert!(start.elapsed() < latency);
+    }
+
+    #[test]
+    fn nearby_devices_are_only_found_by_an_inquiry() {
+        let backend = mock();
+        backend.add_nearby_device(speaker());
+
+        let known = backend.discover_devices().unwrap();
+        assert_eq!(known.iter().map(|device| device.mac_address()).collect::<Vec<_>>(), [HEADPHONES]);
+        assert!(backend.get_device(SPEAKER).is_err());
+
+        let mut found = Vec::new();
+        backend.search_devices(&DiscoverOptions::new().inquiry(Duration::from_secs(1)), &mut |device| found.push(device.mac_address())).unwrap();
+        found.sort();
+        assert_eq!(found, [HEADPHONES, SPEAKER]);
+
+        // known from now on
+        assert_eq!(backend.discover_devices().unwrap().len(), 2);
+        assert!(backend.get_device(SPEAKER).unwrap().last_seen().is_some());
+    }
+
+    #[test]
+    fn operations_change_the_device_state() {
+        let backend = MockBackend::with_devices([MockDevice::new(SPEAKER)]);
+
+        backend.connect_to_device(SPEAKER).unwrap();
+        assert_eq!(flags(&backend, SPEAKER), (true, true, true));
+        assert!(backend.device(SPEAKER).unwrap().last_used().is_some());
+
+        backend.disconnect_device(SPEAKER).unwrap();
+        assert_eq!(flags(&backend, SPEAKER), (false, true, true));
+
+        backend.connect_to_device(SPEAKER).unwrap();
+        backend.remove_device(SPEAKER).unwrap();
+        assert_eq!(flags(&backend, SPEAKER), (false, false, false));
+
+        let err = backend.remove_device(SPEAKER).unwrap_err();
+        assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::NotRemembered, Operation::Remove, Some(SPEAKER)));
+
+        for err in [
+            backend.connect_to_device(HEADPHONES).unwrap_err(),
+            backend.disconnect_device(HEADPHONES).unwrap_err(),
+            backend.remove_device(HEADPHONES).unwrap_err(),
+        ] {
+            assert_eq!((err.kind(), err.mac_address()), (ErrorKind::InvalidMacAddress, Some(HEADPHONES)));
+        }
+    }
+
+    /// Gives the same answers to every request, and keeps track of what it was asked
+    #[derive(Default)]
+    struct ScriptedAgent {
+        pin: Option<String>,
+        passkey: Option<u32>,
+        confirm: bool,
+        asked: Mutex<Vec<&'static str>>,
+    }
+
+    impl PairingAgent for ScriptedAgent {
+        fn request_pin(&self, _: MacAddress) -> Option<String> {
+            self.asked.lock().unwrap().push("pin");
+            self.pin.clone()
+        }
+
+        fn request_passkey(&self, _: MacAddress) -> Option<u32> {
+            self.asked.lock().unwrap().push("passkey");
+            self.passkey
+        }
+
+        fn confirm_passkey(&self, _: MacAddress, _: u32) -> bool {
+            self.asked.lock().unwrap().push("confirm");
+            self.confirm
+        }
+
+        fn display_passkey(&self, _: MacAddress, _: u32) {
+            self.asked.lock().unwrap().push("display");
+        }
+    }
+
+    #[test]
+    fn pairing_asks_the_agent() {
+        let agent = Arc::new(ScriptedAgent { pin: Some("0000".into()), passkey: Some(4321), confirm: true, ..ScriptedAgent::default() });
+
+        for (pairing, asked) in [
+            (MockPairing::Pin("0000".into()), "pin"),
+            (MockPairing::EnterPasskey(4321), "passkey"),
+            (MockPairing::ConfirmPasskey(123456), "confirm"),
+            (MockPairing::DisplayPasskey(98765), "display"),
+        ] {
+            let backend = MockBackend::with_devices([MockDevice::new(SPEAKER)]);
+            backend.set_pairing(SPEAKER, pairing);
+            backend.set_pairing_agent(agent.clone());
+
+            backend.connect_to_device(SPEAKER).unwrap();
+            assert_eq!(agent.asked.lock().unwrap().pop(), Some(asked));
+
+            // paired now, so reconnecting doesn't ask again
+            backend.disconnect_device(SPEAKER).unwrap();
+            backend.connect_to_device(SPEAKER).unwrap();
+            assert!(agent.asked.lock().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn declined_pairing_is_cancelled() {
+        let agent = Arc::new(ScriptedAgent { pin: Some("1234".into()), ..ScriptedAgent::default() });
+
+        for pairing in [MockPairing::Pin("0000".into()), MockPairing::EnterPasskey(4321), MockPairing::ConfirmPasskey(123456)] {
+            let backend = MockBackend::with_devices([MockDevice::new(SPEAKER)]);
+            backend.set_pairing(SPEAKER, pairing);
+            backend.set_pairing_agent(agent.clone());
+
+            let err = backend.connect_to_device(SPEAKER).unwrap_err();
+
+            assert_eq!((err.kind(), err.operation(), err.mac_address()), (ErrorKind::Cancelled, Operation::Connect, Some(SPEAKER)));
+            assert_eq!(flags(&backend, SPEAKER), (false, false, false));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use crate::{BluetoothDevice, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, PairingAgent};
+
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(target_os = "linux")]
+mod bluez;
+#[cfg(feature = "fault")]
+mod fault;
+mod mock;
+#[cfg(test)]
+pub(crate) mod test_devices;
+#[cfg(feature = "record")]
+mod record;
+#[cfg(feature = "record")]
+mod replay;
+#[cfg(windows)]
+mod win32;
+
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+#[cfg(target_os = "linux")]
+pub use bluez::BluezBackend;
+#[cfg(feature = "fault")]
+pub use fault::*;
+pub use mock::{MockBackend, MockDevice, MockPairing};
+#[cfg(feature = "record")]
+pub use record::*;
+#[cfg(feature = "record")]
+pub use replay::ReplayBackend;
+#[cfg(windows)]
+pub use win32::Win32Backend;
+
+/// An operation of a [`BluetoothBackend`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Operation {
+    Discover,
+    /// Looking up a single device
+    Get,
+    Connect,
+    Disconnect,
+    Remove,
+}
+
+/// The set of operations the app needs from a bluetooth stack.
+///
+/// Every method is blocking, so callers should run them off of the UI thread.
+pub trait BluetoothBackend: Send + Sync {
+    /// Lists the known devices, same as [`discover_devices_with`](Self::discover_devices_with) the default options.
+    fn discover_devices(&self) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        self.discover_devices_with(&DiscoverOptions::default())
+    }
+
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError>;
+
+    /// Same as [`discover_devices_with`](Self::discover_devices_with), but hands each device to `found` as soon as it's found.
+    ///
+    /// Backends that can't search incrementally hand over every device at the end of the scan.
+    /// Devices found before an error are still handed over.
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        self.discover_devices_with(options)?.into_iter().for_each(found);
+        Ok(())
+    }
+
+    /// Looks up the current state of a single device, which is much quicker than a scan.
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError>;
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError>;
+
+    /// Drops the connection to a device, but keeps it paired, unlike [`remove_device`](Self::remove_device).
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError>;
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError>;
+
+    /// Pairs with a device from scratch, removing its existing pairing first, which requires the device to be in pairing mode.
+    ///
+    /// Only needed when the existing pairing is broken, e.g. after the device was reset,
+    /// since [`connect_to_device`](Self::connect_to_device) reconnects paired devices without pairing again.
+    fn pair_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        match self.remove_device(mac_address) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotRemembered => {}
+            Err(err) => return Err(err),
+        }
+
+        self.connect_to_device(mac_address)
+    }
+
+    /// Answers pairing requests with `agent` from now on, instead of the OS's pairing prompts.
+    ///
+    /// Backends which can't pair interactively ignore the agent, leaving pairing to the OS.
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        let _ = agent;
+    }
+}
+
+impl<B: BluetoothBackend + ?Sized> BluetoothBackend for Arc<B> {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        (**self).discover_devices_with(options)
+    }
+
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        (**self).search_devices(options, found)
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        (**self).get_device(mac_address)
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        (**self).connect_to_device(mac_address)
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        (**self).disconnect_device(mac_address)
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        (**self).remove_device(mac_address)
+    }
+
+    fn pair_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        (**self).pair_device(mac_address)
+    }
+
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        (**self).set_pairing_agent(agent)
+    }
+}
+
+/// Returns the backend for the bluetooth stack of the current platform, if there is one.
+pub fn platform_backend() -> Option<Arc<dyn BluetoothBackend>> {
+    #[cfg(windows)]
+    return Some(Arc::new(Win32Backend::new()));
+
+    // the system bus or BlueZ may not be available, e.g. in containers
+    #[cfg(target_os = "linux")]
+    return BluezBackend::new().ok().map(|backend| Arc::new(backend) as _);
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    return None;
+}
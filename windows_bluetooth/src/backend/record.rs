@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, PairingAgent};
+
+/// A single backend call, as written to a recording, one JSON object per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Local time the call started at
+    pub at: NaiveDateTime,
+    /// Time since the recording started
+    pub elapsed: Duration,
+    /// How long the call took
+    pub duration: Duration,
+    pub outcome: RecordedOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedOutcome {
+    Discover {
+        options: DiscoverOptions,
+        result: Result<Vec<BluetoothDevice>, RecordedError>,
+    },
+    Get {
+        mac_address: MacAddress,
+        result: Result<BluetoothDevice, RecordedError>,
+    },
+    Connect {
+        mac_address: MacAddress,
+        result: Result<(), RecordedError>,
+    },
+    Disconnect {
+        mac_address: MacAddress,
+        result: Result<(), RecordedError>,
+    },
+    Remove {
+        mac_address: MacAddress,
+        result: Result<(), RecordedError>,
+    },
+}
+
+/// An error returned by a recorded call; platform errors are kept as their message, and code if there is one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedError {
+    OutOfMemory,
+    BluetoothUnavailable,
+    InvalidMacAddress,
+    Cancelled,
+    AccessDenied,
+    NotRemembered,
+    NoServices,
+    Unhandled {
+        code: Option<u32>,
+        message: String,
+    },
+}
+
+/// Wraps another backend and records the outcome of every call to it.
+pub struct RecordingBackend<B> {
+    inner: B,
+    writer: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+impl<B: BluetoothBackend> RecordingBackend<B> {
+    pub fn new(inner: B, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            writer: Mutex::new(Box::new(writer)),
+            started: Instant::now(),
+        }
+    }
+
+    /// Records to a new file at `path`, truncating it if it exists.
+    pub fn create(inner: B, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(inner, BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn record<T>(&self, call: impl FnOnce(&B) -> T, outcome: impl FnOnce(&T) -> RecordedOutcome) -> T {
+        let at = Local::now().naive_local();
+        let start = Instant::now();
+
+        let res = call(&self.inner);
+
+        let event = RecordedEvent {
+            at,
+            elapsed: start.duration_since(self.started),
+            duration: start.elapsed(),
+            outcome: outcome(&res),
+        };
+
+        let mut writer = self.writer.lock().expect("recording writer shouldn't be poisoned");
+
+        // recording is best effort, failing to write it shouldn't fail the operation itself
+        let _ = serde_json::to_writer(&mut *writer, &event)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writer.flush());
+
+        res
+    }
+}
+
+impl<B: BluetoothBackend> BluetoothBackend for RecordingBackend<B> {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        self.record(
+            |inner| inner.discover_devices_with(options),
+            |res| RecordedOutcome::Discover {
+                options: *options,
+                result: match res {
+                    Ok(devices) => Ok(devices.clone()),
+                    Err(err) => Err(err.into()),
+                },
+            },
+        )
+    }
+
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        let (res, _) = self.record(
+            |inner| {
+                let mut devices = Vec::new();
+
+                let res = inner.search_devices(options, &mut |device| {
+                    devices.push(device.clone());
+                    found(device);
+                });
+
+                (res, devices)
+            },
+            // recorded as a whole scan, since that's how it's replayed
+            |(res, devices)| RecordedOutcome::Discover {
+                options: *options,
+                result: match res {
+                    Ok(()) => Ok(devices.clone()),
+                    Err(err) => Err(err.into()),
+                },
+            },
+        );
+
+        res
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        self.record(
+            |inner| inner.get_device(mac_address),
+            |res| RecordedOutcome::Get {
+                mac_address,
+                result: res.as_ref().cloned().map_err(RecordedError::from),
+            },
+        )
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.record(
+            |inner| inner.connect_to_device(mac_address),
+            |res| RecordedOutcome::Connect {
+                mac_address,
+                result: res.as_ref().map(|_| ()).map_err(RecordedError::from),
+            },
+        )
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.record(
+            |inner| inner.disconnect_device(mac_address),
+            |res| RecordedOutcome::Disconnect {
+                mac_address,
+                result: res.as_ref().map(|_| ()).map_err(RecordedError::from),
+            },
+        )
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        self.record(
+            |inner| inner.remove_device(mac_address),
+            |res| RecordedOutcome::Remove {
+                mac_address,
+                result: res.as_ref().map(|_| ()).map_err(RecordedError::from),
+            },
+        )
+    }
+
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        self.inner.set_pairing_agent(agent)
+    }
+}
+
+impl From<&BluetoothError> for RecordedError {
+    fn from(err: &BluetoothError) -> Self {
+        match err.kind() {
+            ErrorKind::OutOfMemory => RecordedError::OutOfMemory,
+            ErrorKind::BluetoothUnavailable => RecordedError::BluetoothUnavailable,
+            ErrorKind::InvalidMacAddress => RecordedError::InvalidMacAddress,
+            ErrorKind::Cancelled => RecordedError::Cancelled,
+            ErrorKind::AccessDenied => RecordedError::AccessDenied,
+            ErrorKind::NotRemembered => RecordedError::NotRemembered,
+            ErrorKind::NoServices => RecordedError::NoServices,
+            ErrorKind::Unhandled => RecordedError::Unhandled {
+                code: err.code(),
+                // the innermost error is the one from the platform itself
+                message: std::iter::successors(err.source(), |&err| err.source())
+                    .last()
+                    .map_or_else(|| err.to_string(), ToString::to_string),
+            },
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, Operation, RecordedError, RecordedEvent, RecordedOutcome};
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    discoveries: VecDeque<RecordedEvent>,
+    /// The last scan replayed with each set of options
+    last_discoveries: HashMap<DiscoverOptions, RecordedEvent>,
+    gets: VecDeque<RecordedEvent>,
+    connects: VecDeque<RecordedEvent>,
+    disconnects: VecDeque<RecordedEvent>,
+    removes: VecDeque<RecordedEvent>,
+}
+
+/// Feeds a recording made by [`RecordingBackend`](crate::RecordingBackend) back as a backend.
+///
+/// Scans return the recorded scans made with the same options in order, repeating the last one once they run out,
+/// or nothing if there's no scan with those options. Lookups, connects, disconnects and removes return the next
+/// recorded outcome for the same MAC address, or `InvalidMacAddress` if the app diverged from the recording and there is none.
+#[derive(Debug, Default)]
+pub struct ReplayBackend {
+    state: Mutex<ReplayState>,
+    recorded_latency: bool,
+}
+
+impl ReplayBackend {
+    pub fn from_events(events: impl IntoIterator<Item = RecordedEvent>) -> Self {
+        let mut state = ReplayState::default();
+
+        for event in events {
+            match event.outcome {
+                RecordedOutcome::Discover { .. } => state.discoveries.push_back(event),
+                RecordedOutcome::Get { .. } => state.gets.push_back(event),
+                RecordedOutcome::Connect { .. } => state.connects.push_back(event),
+                RecordedOutcome::Disconnect { .. } => state.disconnects.push_back(event),
+                RecordedOutcome::Remove { .. } => state.removes.push_back(event),
+            }
+        }
+
+        Self {
+            state: Mutex::new(state),
+            recorded_latency: false,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let events = BufReader::new(File::open(path)?)
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|line| serde_json::from_str(&line?).map_err(io::Error::from))
+            .collect::<io::Result<Vec<RecordedEvent>>>()?;
+
+        Ok(Self::from_events(events))
+    }
+
+    /// Makes each call take as long as it did when it was recorded.
+    pub fn with_recorded_latency(mut self) -> Self {
+        self.recorded_latency = true;
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().expect("replay state shouldn't be poisoned")
+    }
+
+    fn wait(&self, event: &RecordedEvent) {
+        if self.recorded_latency {
+            thread::sleep(event.duration);
+        }
+    }
+
+    fn take_for(queue: &mut VecDeque<RecordedEvent>, mac_address: MacAddress) -> Option<RecordedEvent> {
+        let index = queue.iter().position(|event| match event.outcome {
+            RecordedOutcome::Get { mac_address: addr, .. }
+            | RecordedOutcome::Connect { mac_address: addr, .. }
+            | RecordedOutcome::Disconnect { mac_address: addr, .. }
+            | RecordedOutcome::Remove { mac_address: addr, .. } => addr == mac_address,
+            RecordedOutcome::Discover { .. } => false,
+        })?;
+
+        queue.remove(index)
+    }
+}
+
+impl BluetoothBackend for ReplayBackend {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        let event = {
+            let mut state = self.state();
+
+            let index = state.discoveries.iter().position(|event| matches!(
+                &event.outcome,
+                RecordedOutcome::Discover { options: recorded, .. } if recorded == options
+            ));
+
+            match index.and_then(|index| state.discoveries.remove(index)) {
+                Some(event) => {
+                    state.last_discoveries.insert(*options, event.clone());
+                    event
+                }
+                None => match state.last_discoveries.get(options) {
+                    Some(event) => event.clone(),
+                    None => return Ok(Vec::new()),
+                },
+            }
+        };
+
+        self.wait(&event);
+
+        // the recorded scans were already filtered by their options
+        match event.outcome {
+            RecordedOutcome::Discover { result: Ok(devices), .. } => Ok(devices),
+            RecordedOutcome::Discover { result: Err(err), .. } => Err(err.replay(Operation::Discover, None)),
+            _ => unreachable!("only discoveries are queued as discoveries"),
+        }
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        let event = Self::take_for(&mut self.state().gets, mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Get).with_mac_address(mac_address))?;
+
+        self.wait(&event);
+
+        match event.outcome {
+            RecordedOutcome::Get { result, .. } => result.map_err(|err| err.replay(Operation::Get, Some(mac_address))),
+            _ => unreachable!("only lookups are queued as lookups"),
+        }
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        let event = Self::take_for(&mut self.state().connects, mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Connect).with_mac_address(mac_address))?;
+
+        self.wait(&event);
+
+        match event.outcome {
+            RecordedOutcome::Connect { result, .. } => result.map_err(|err| err.replay(Operation::Connect, Some(mac_address))),
+            _ => unreachable!("only connects are queued as connects"),
+        }
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        let event = Self::take_for(&mut self.state().disconnects, mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Disconnect).with_mac_address(mac_address))?;
+
+        self.wait(&event);
+
+        match event.outcome {
+            RecordedOutcome::Disconnect { result, .. } => result.map_err(|err| err.replay(Operation::Disconnect, Some(mac_address))),
+            _ => unreachable!("only disconnects are queued as disconnects"),
+        }
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        let event = Self::take_for(&mut self.state().removes, mac_address)
+            .ok_or_else(|| BluetoothError::new(ErrorKind::InvalidMacAddress, Operation::Remove).with_mac_address(mac_address))?;
+
+        self.wait(&event);
+
+        match event.outcome {
+            RecordedOutcome::Remove { result, .. } => result.map_err(|err| err.replay(Operation::Remove, Some(mac_address))),
+            _ => unreachable!("only removes are queued as removes"),
+        }
+    }
+}
+
+impl RecordedError {
+    fn replay(self, operation: Operation, mac_address: Option<MacAddress>) -> BluetoothError {
+        let kind = match self {
+            RecordedError::OutOfMemory => ErrorKind::OutOfMemory,
+            RecordedError::BluetoothUnavailable => ErrorKind::BluetoothUnavailable,
+            RecordedError::InvalidMacAddress => ErrorKind::InvalidMacAddress,
+            RecordedError::Cancelled => ErrorKind::Cancelled,
+            RecordedError::AccessDenied => ErrorKind::AccessDenied,
+            RecordedError::NotRemembered => ErrorKind::NotRemembered,
+            RecordedError::NoServices => ErrorKind::NoServices,
+            RecordedError::Unhandled { .. } => ErrorKind::Unhandled,
+        };
+
+        let mut err = BluetoothError::new(kind, operation);
+
+        if let Some(mac_address) = mac_address {
+            err = err.with_mac_address(mac_address);
+        }
+
+        if let RecordedError::Unhandled { code, message } = self {
+            err = err.with_source(message);
+
+            if let Some(code) = code {
+                err = err.with_code(code);
+            }
+        }
+
+        err
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::{BluetoothBackend, BluetoothError, DiscoverOptions, ErrorKind, MacAddress, MockBackend, Operation, RecordedEvent, RecordingBackend, ServiceUuid};
+    use crate::backend::test_devices::{headphones, speaker, HEADPHONES, SPEAKER};
+    use super::ReplayBackend;
+
+    /// A recording kept in memory, which stays readable while the recording backend owns a clone of it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The parts of an error a replay reproduces
+    type ErrorParts = (ErrorKind, Operation, Option<MacAddress>, Option<u32>, String);
+
+    fn error_parts<T>(res: Result<T, BluetoothError>) -> Result<T, ErrorParts> {
+        res.map_err(|err| (err.kind(), err.operation(), err.mac_address(), err.code(), err.to_string()))
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let mock = MockBackend::with_devices([headphones().connected(true)]);
+        mock.add_nearby_device(speaker());
+        mock.fail_next(BluetoothError::new(ErrorKind::Unhandled, Operation::Disconnect).with_mac_address(HEADPHONES).with_code(31).with_source("device broke"));
+
+        let buffer = SharedBuffer::default();
+        let recorder = RecordingBackend::new(mock, buffer.clone());
+        let deep = DiscoverOptions::new().inquiry(Duration::from_secs(1));
+
+        let known = recorder.discover_devices().unwrap();
+        let found = recorder.discover_devices_with(&deep).unwrap();
+        let speaker = recorder.get_device(SPEAKER).unwrap();
+        let connected = error_parts(recorder.connect_to_device(SPEAKER));
+        let broke = error_parts(recorder.disconnect_device(HEADPHONES));
+        let removed = error_parts(recorder.remove_device(SPEAKER));
+        let not_remembered = error_parts(recorder.remove_device(SPEAKER));
+
+        assert_eq!(known.len(), 1);
+        assert_eq!(found.len(), 2);
+        assert!(broke.is_err() && not_remembered.is_err());
+
+        let events = buffer.0.lock().unwrap()
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<RecordedEvent>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        let replay = ReplayBackend::from_events(events);
+
+        // scans are matched by their options, not by the order they were made in
+        assert_eq!(replay.discover_devices_with(&deep).unwrap(), found);
+        assert_eq!(replay.discover_devices().unwrap(), known);
+        assert_eq!(replay.discover_devices().unwrap(), known, "the last scan with the same options should repeat");
+        assert!(replay.discover_devices_with(&DiscoverOptions::new().connected(false)).unwrap().is_empty());
+
+        assert_eq!(replay.get_device(SPEAKER).unwrap(), speaker);
+        assert_eq!(error_parts(replay.connect_to_device(SPEAKER)), connected);
+        assert_eq!(error_parts(replay.disconnect_device(HEADPHONES)), broke);
+        assert_eq!(error_parts(replay.remove_device(SPEAKER)), removed);
+        assert_eq!(error_parts(replay.remove_device(SPEAKER)), not_remembered);
+
+        // diverged from the recording
+        let err = replay.connect_to_device(HEADPHONES).unwrap_err();
+        assert_eq!((err.kind(), err.operation()), (ErrorKind::InvalidMacAddress, Operation::Connect));
+    }
+
+    #[test]
+    fn recordings_use_the_crate_serde_format() {
+        let mock = MockBackend::with_devices([headphones().service(ServiceUuid::from_short(0x110B))]);
+        mock.fail_next(BluetoothError::new(ErrorKind::BluetoothUnavailable, Operation::Connect));
+
+        let buffer = SharedBuffer::default();
+        let recorder = RecordingBackend::new(mock, buffer.clone());
+
+        recorder.get_device(HEADPHONES).unwrap();
+        recorder.connect_to_device(HEADPHONES).unwrap_err();
+
+        let recording = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let (get, connect) = recording.split_once('\n').unwrap();
+
+        assert!(get.contains(r#""mac_address":"00:1A:7D:DA:71:13""#), "{get}");
+        assert!(get.contains(r#""0000110b-0000-1000-8000-00805f9b34fb""#), "{get}");
+        assert!(connect.contains("BluetoothUnavailable"), "{connect}");
+
+        // a corrupt service fails the whole event instead of replaying a different device
+        let corrupt = get.replace("0000110b-", "0000110x-");
+        assert!(serde_json::from_str::<RecordedEvent>(&corrupt).is_err());
+    }
+}
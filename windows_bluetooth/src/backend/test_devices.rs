@@ -0,0 +1,21 @@
+//! Devices shared by the tests, so each test module doesn't make up its own.
+
+use crate::{mac, MacAddress, MockBackend, MockDevice};
+
+pub(crate) const HEADPHONES: MacAddress = mac!("00:1A:7D:DA:71:13");
+pub(crate) const SPEAKER: MacAddress = mac!("34:88:5D:10:2B:C4");
+
+/// Paired headphones, which aren't connected
+pub(crate) fn headphones() -> MockDevice {
+    MockDevice::new(HEADPHONES).name("WH-1000XM4").class(0x240418).remembered(true).authenticated(true)
+}
+
+/// A speaker which was never paired
+pub(crate) fn speaker() -> MockDevice {
+    MockDevice::new(SPEAKER).name("Speaker")
+}
+
+/// A backend which only knows the [`headphones`]
+pub(crate) fn mock() -> MockBackend {
+    MockBackend::with_devices([headphones()])
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use crate::{connect_to_device_os, DeviceSearch, disconnect_device, discover_devices, get_device, pair_device_os, remove_device, BluetoothBackend, BluetoothDevice, BluetoothError, DiscoverOptions, MacAddress, PairingAgent, Radio};
+use crate::pairing::AgentSlot;
+
+/// Backend over the Win32 bluetooth api.
+///
+/// Clones share the pairing agent, which is set on any of them.
+#[derive(Debug, Default, Clone)]
+pub struct Win32Backend {
+    radio: Option<Radio>,
+    agent: AgentSlot,
+}
+
+impl Win32Backend {
+    /// Uses whichever radio Windows picks for each call.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends every call through `radio`, e.g. a USB dongle instead of a built-in adapter.
+    pub fn with_radio(radio: Radio) -> Self {
+        Self {
+            radio: Some(radio),
+            agent: AgentSlot::default(),
+        }
+    }
+
+    pub fn radio(&self) -> Option<&Radio> {
+        self.radio.as_ref()
+    }
+}
+
+impl BluetoothBackend for Win32Backend {
+    fn discover_devices_with(&self, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        discover_devices(self.radio.as_ref(), options)
+    }
+
+    fn search_devices(&self, options: &DiscoverOptions, found: &mut dyn FnMut(BluetoothDevice)) -> Result<(), BluetoothError> {
+        for device in DeviceSearch::new(self.radio.as_ref(), options)? {
+            found(device?);
+        }
+
+        Ok(())
+    }
+
+    fn get_device(&self, mac_address: MacAddress) -> Result<BluetoothDevice, BluetoothError> {
+        get_device(mac_address, self.radio.as_ref())
+    }
+
+    fn connect_to_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        connect_to_device_os(mac_address, self.radio.as_ref(), self.agent.get().as_deref())
+    }
+
+    fn disconnect_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        disconnect_device(mac_address, self.radio.as_ref())
+    }
+
+    fn remove_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        remove_device(mac_address, self.radio.as_ref())
+    }
+
+    fn pair_device(&self, mac_address: MacAddress) -> Result<(), BluetoothError> {
+        pair_device_os(mac_address, self.radio.as_ref(), self.agent.get().as_deref())
+    }
+
+    fn set_pairing_agent(&self, agent: Arc<dyn PairingAgent>) {
+        self.agent.set(agent);
+    }
+}
@@ -0,0 +1,578 @@
+use std::fmt;
+
+/// A device's Class of Device, as advertised during inquiry.
+///
+/// Bits 2-7 are the minor class, whose meaning depends on the major class in bits 8-12, and bits 13-23 are the
+/// service classes.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct DeviceClass(u32);
+
+impl DeviceClass {
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    pub const fn major(self) -> MajorClass {
+        match (self.0 >> 8) & 0x1F {
+            0 => MajorClass::Miscellaneous,
+            1 => MajorClass::Computer,
+            2 => MajorClass::Phone,
+            3 => MajorClass::NetworkAccessPoint,
+            4 => MajorClass::AudioVideo,
+            5 => MajorClass::Peripheral,
+            6 => MajorClass::Imaging,
+            7 => MajorClass::Wearable,
+            8 => MajorClass::Toy,
+            9 => MajorClass::Health,
+            31 => MajorClass::Uncategorized,
+            other => MajorClass::Reserved(other as u8),
+        }
+    }
+
+    pub const fn minor(self) -> MinorClass {
+        let minor = ((self.0 >> 2) & 0x3F) as u8;
+
+        match self.major() {
+            MajorClass::Computer => MinorClass::Computer(match minor {
+                0 => ComputerClass::Uncategorized,
+                1 => ComputerClass::Desktop,
+                2 => ComputerClass::Server,
+                3 => ComputerClass::Laptop,
+                4 => ComputerClass::Handheld,
+                5 => ComputerClass::PalmSize,
+                6 => ComputerClass::Wearable,
+                7 => ComputerClass::Tablet,
+                other => ComputerClass::Reserved(other),
+            }),
+            MajorClass::Phone => MinorClass::Phone(match minor {
+                0 => PhoneClass::Uncategorized,
+                1 => PhoneClass::Cellular,
+                2 => PhoneClass::Cordless,
+                3 => PhoneClass::Smartphone,
+                4 => PhoneClass::Modem,
+                5 => PhoneClass::Isdn,
+                other => PhoneClass::Reserved(other),
+            }),
+            MajorClass::AudioVideo => MinorClass::AudioVideo(match minor {
+                0 => AudioVideoClass::Uncategorized,
+                1 => AudioVideoClass::Headset,
+                2 => AudioVideoClass::Handsfree,
+                4 => AudioVideoClass::Microphone,
+                5 => AudioVideoClass::Loudspeaker,
+                6 => AudioVideoClass::Headphones,
+                7 => AudioVideoClass::PortableAudio,
+                8 => AudioVideoClass::CarAudio,
+                9 => AudioVideoClass::SetTopBox,
+                10 => AudioVideoClass::HiFiAudio,
+                11 => AudioVideoClass::Vcr,
+                12 => AudioVideoClass::VideoCamera,
+                13 => AudioVideoClass::Camcorder,
+                14 => AudioVideoClass::VideoMonitor,
+                15 => AudioVideoClass::VideoDisplayAndLoudspeaker,
+                16 => AudioVideoClass::VideoConferencing,
+                18 => AudioVideoClass::GamingToy,
+                other => AudioVideoClass::Reserved(other),
+            }),
+            // the upper two bits say whether it's a keyboard and/or pointing device, the lower four what else it is
+            MajorClass::Peripheral => MinorClass::Peripheral {
+                keyboard: minor & 0x10 != 0,
+                pointing: minor & 0x20 != 0,
+                kind: match minor & 0x0F {
+                    0 => PeripheralKind::Uncategorized,
+                    1 => PeripheralKind::Joystick,
+                    2 => PeripheralKind::Gamepad,
+                    3 => PeripheralKind::RemoteControl,
+                    4 => PeripheralKind::SensingDevice,
+                    5 => PeripheralKind::DigitizerTablet,
+                    6 => PeripheralKind::CardReader,
+                    7 => PeripheralKind::DigitalPen,
+                    8 => PeripheralKind::HandheldScanner,
+                    9 => PeripheralKind::GesturalInput,
+                    other => PeripheralKind::Reserved(other),
+                },
+            },
+            // each of the upper four bits is a capability, and a device can have several
+            MajorClass::Imaging => MinorClass::Imaging {
+                display: minor & 0x04 != 0,
+                camera: minor & 0x08 != 0,
+                scanner: minor & 0x10 != 0,
+                printer: minor & 0x20 != 0,
+            },
+            MajorClass::Wearable => MinorClass::Wearable(match minor {
+                1 => WearableClass::Wristwatch,
+                2 => WearableClass::Pager,
+                3 => WearableClass::Jacket,
+                4 => WearableClass::Helmet,
+                5 => WearableClass::Glasses,
+                other => WearableClass::Other(other),
+            }),
+            MajorClass::Toy => MinorClass::Toy(match minor {
+                1 => ToyClass::Robot,
+                2 => ToyClass::Vehicle,
+                3 => ToyClass::Doll,
+                4 => ToyClass::Controller,
+                5 => ToyClass::Game,
+                other => ToyClass::Other(other),
+            }),
+            _ => MinorClass::Other(minor),
+        }
+    }
+
+    pub const fn has_service(self, service: ServiceClass) -> bool {
+        self.0 & service.bit() != 0
+    }
+
+    /// The service classes the device advertises, in bit order
+    pub fn services(self) -> impl Iterator<Item = ServiceClass> {
+        ServiceClass::ALL.into_iter().filter(move |&service| self.has_service(service))
+    }
+}
+
+impl From<u32> for DeviceClass {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<DeviceClass> for u32 {
+    fn from(class: DeviceClass) -> Self {
+        class.0
+    }
+}
+
+impl fmt::Display for DeviceClass {
+    /// The most specific description available, like "Headphones" rather than "Audio/Video"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.minor() {
+            MinorClass::Computer(ComputerClass::Uncategorized | ComputerClass::Reserved(_))
+            | MinorClass::Phone(PhoneClass::Uncategorized | PhoneClass::Reserved(_))
+            | MinorClass::AudioVideo(AudioVideoClass::Uncategorized | AudioVideoClass::Reserved(_))
+            | MinorClass::Peripheral { keyboard: false, pointing: false, kind: PeripheralKind::Uncategorized | PeripheralKind::Reserved(_) }
+            | MinorClass::Imaging { display: false, camera: false, scanner: false, printer: false }
+            | MinorClass::Wearable(WearableClass::Other(_))
+            | MinorClass::Toy(ToyClass::Other(_))
+            | MinorClass::Other(_) => fmt::Display::fmt(&self.major(), f),
+            minor => fmt::Display::fmt(&minor, f),
+        }
+    }
+}
+
+impl fmt::Debug for DeviceClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DeviceClass({:#08X}: {self})", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum MajorClass {
+    Miscellaneous,
+    Computer,
+    Phone,
+    NetworkAccessPoint,
+    AudioVideo,
+    Peripheral,
+    Imaging,
+    Wearable,
+    Toy,
+    Health,
+    Uncategorized,
+    Reserved(u8),
+}
+
+impl fmt::Display for MajorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MajorClass::Miscellaneous => write!(f, "Miscellaneous"),
+            MajorClass::Computer => write!(f, "Computer"),
+            MajorClass::Phone => write!(f, "Phone"),
+            MajorClass::NetworkAccessPoint => write!(f, "Network Access Point"),
+            MajorClass::AudioVideo => write!(f, "Audio/Video"),
+            MajorClass::Peripheral => write!(f, "Peripheral"),
+            MajorClass::Imaging => write!(f, "Imaging"),
+            MajorClass::Wearable => write!(f, "Wearable"),
+            MajorClass::Toy => write!(f, "Toy"),
+            MajorClass::Health => write!(f, "Health"),
+            MajorClass::Uncategorized => write!(f, "Uncategorized"),
+            MajorClass::Reserved(major) => write!(f, "Reserved ({major})"),
+        }
+    }
+}
+
+/// The minor class, decoded according to the major class.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum MinorClass {
+    Computer(ComputerClass),
+    Phone(PhoneClass),
+    AudioVideo(AudioVideoClass),
+    Peripheral {
+        keyboard: bool,
+        pointing: bool,
+        kind: PeripheralKind,
+    },
+    Imaging {
+        display: bool,
+        camera: bool,
+        scanner: bool,
+        printer: bool,
+    },
+    Wearable(WearableClass),
+    Toy(ToyClass),
+    /// The raw minor class of a major class without decoded minor classes
+    Other(u8),
+}
+
+impl fmt::Display for MinorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinorClass::Computer(class) => fmt::Display::fmt(class, f),
+            MinorClass::Phone(class) => fmt::Display::fmt(class, f),
+            MinorClass::AudioVideo(class) => fmt::Display::fmt(class, f),
+            MinorClass::Peripheral { keyboard, pointing, kind } => {
+                let input = match (keyboard, pointing) {
+                    (true, true) => Some("Keyboard and Mouse"),
+                    (true, false) => Some("Keyboard"),
+                    (false, true) => Some("Mouse"),
+                    (false, false) => None,
+                };
+
+                match (input, kind) {
+                    (Some(input), PeripheralKind::Uncategorized) => write!(f, "{input}"),
+                    (Some(input), kind) => write!(f, "{input} ({kind})"),
+                    (None, kind) => write!(f, "{kind}"),
+                }
+            }
+            MinorClass::Imaging { display, camera, scanner, printer } => {
+                let capabilities = [(display, "Display"), (camera, "Camera"), (scanner, "Scanner"), (printer, "Printer")]
+                    .into_iter()
+                    .filter_map(|(&has, name)| has.then_some(name))
+                    .collect::<Vec<_>>();
+
+                if capabilities.is_empty() {
+                    write!(f, "Imaging")
+                } else {
+                    write!(f, "{}", capabilities.join("/"))
+                }
+            }
+            MinorClass::Wearable(class) => fmt::Display::fmt(class, f),
+            MinorClass::Toy(class) => fmt::Display::fmt(class, f),
+            MinorClass::Other(minor) => write!(f, "Minor Class {minor}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum ComputerClass {
+    Uncategorized,
+    Desktop,
+    Server,
+    Laptop,
+    Handheld,
+    PalmSize,
+    Wearable,
+    Tablet,
+    Reserved(u8),
+}
+
+impl fmt::Display for ComputerClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComputerClass::Uncategorized => write!(f, "Computer"),
+            ComputerClass::Desktop => write!(f, "Desktop"),
+            ComputerClass::Server => write!(f, "Server"),
+            ComputerClass::Laptop => write!(f, "Laptop"),
+            ComputerClass::Handheld => write!(f, "Handheld Computer"),
+            ComputerClass::PalmSize => write!(f, "Palm-Size Computer"),
+            ComputerClass::Wearable => write!(f, "Wearable Computer"),
+            ComputerClass::Tablet => write!(f, "Tablet"),
+            ComputerClass::Reserved(minor) => write!(f, "Computer ({minor})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum PhoneClass {
+    Uncategorized,
+    Cellular,
+    Cordless,
+    Smartphone,
+    Modem,
+    Isdn,
+    Reserved(u8),
+}
+
+impl fmt::Display for PhoneClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhoneClass::Uncategorized => write!(f, "Phone"),
+            PhoneClass::Cellular => write!(f, "Cellular Phone"),
+            PhoneClass::Cordless => write!(f, "Cordless Phone"),
+            PhoneClass::Smartphone => write!(f, "Smartphone"),
+            PhoneClass::Modem => write!(f, "Modem"),
+            PhoneClass::Isdn => write!(f, "ISDN Access"),
+            PhoneClass::Reserved(minor) => write!(f, "Phone ({minor})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum AudioVideoClass {
+    Uncategorized,
+    Headset,
+    Handsfree,
+    Microphone,
+    Loudspeaker,
+    Headphones,
+    PortableAudio,
+    CarAudio,
+    SetTopBox,
+    HiFiAudio,
+    Vcr,
+    VideoCamera,
+    Camcorder,
+    VideoMonitor,
+    VideoDisplayAndLoudspeaker,
+    VideoConferencing,
+    GamingToy,
+    Reserved(u8),
+}
+
+impl fmt::Display for AudioVideoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioVideoClass::Uncategorized => write!(f, "Audio/Video"),
+            AudioVideoClass::Headset => write!(f, "Headset"),
+            AudioVideoClass::Handsfree => write!(f, "Hands-Free"),
+            AudioVideoClass::Microphone => write!(f, "Microphone"),
+            AudioVideoClass::Loudspeaker => write!(f, "Loudspeaker"),
+            AudioVideoClass::Headphones => write!(f, "Headphones"),
+            AudioVideoClass::PortableAudio => write!(f, "Portable Audio"),
+            AudioVideoClass::CarAudio => write!(f, "Car Audio"),
+            AudioVideoClass::SetTopBox => write!(f, "Set-Top Box"),
+            AudioVideoClass::HiFiAudio => write!(f, "HiFi Audio"),
+            AudioVideoClass::Vcr => write!(f, "VCR"),
+            AudioVideoClass::VideoCamera => write!(f, "Video Camera"),
+            AudioVideoClass::Camcorder => write!(f, "Camcorder"),
+            AudioVideoClass::VideoMonitor => write!(f, "Video Monitor"),
+            AudioVideoClass::VideoDisplayAndLoudspeaker => write!(f, "Video Display and Loudspeaker"),
+            AudioVideoClass::VideoConferencing => write!(f, "Video Conferencing"),
+            AudioVideoClass::GamingToy => write!(f, "Gaming/Toy"),
+            AudioVideoClass::Reserved(minor) => write!(f, "Audio/Video ({minor})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum PeripheralKind {
+    Uncategorized,
+    Joystick,
+    Gamepad,
+    RemoteControl,
+    SensingDevice,
+    DigitizerTablet,
+    CardReader,
+    DigitalPen,
+    HandheldScanner,
+    GesturalInput,
+    Reserved(u8),
+}
+
+impl fmt::Display for PeripheralKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PeripheralKind::Uncategorized => write!(f, "Peripheral"),
+            PeripheralKind::Joystick => write!(f, "Joystick"),
+            PeripheralKind::Gamepad => write!(f, "Gamepad"),
+            PeripheralKind::RemoteControl => write!(f, "Remote Control"),
+            PeripheralKind::SensingDevice => write!(f, "Sensing Device"),
+            PeripheralKind::DigitizerTablet => write!(f, "Digitizer Tablet"),
+            PeripheralKind::CardReader => write!(f, "Card Reader"),
+            PeripheralKind::DigitalPen => write!(f, "Digital Pen"),
+            PeripheralKind::HandheldScanner => write!(f, "Handheld Scanner"),
+            PeripheralKind::GesturalInput => write!(f, "Gestural Input Device"),
+            PeripheralKind::Reserved(kind) => write!(f, "Peripheral ({kind})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum WearableClass {
+    Wristwatch,
+    Pager,
+    Jacket,
+    Helmet,
+    Glasses,
+    Other(u8),
+}
+
+impl fmt::Display for WearableClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WearableClass::Wristwatch => write!(f, "Wristwatch"),
+            WearableClass::Pager => write!(f, "Pager"),
+            WearableClass::Jacket => write!(f, "Jacket"),
+            WearableClass::Helmet => write!(f, "Helmet"),
+            WearableClass::Glasses => write!(f, "Glasses"),
+            WearableClass::Other(minor) => write!(f, "Wearable ({minor})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum ToyClass {
+    Robot,
+    Vehicle,
+    Doll,
+    Controller,
+    Game,
+    Other(u8),
+}
+
+impl fmt::Display for ToyClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ToyClass::Robot => write!(f, "Robot"),
+            ToyClass::Vehicle => write!(f, "Vehicle"),
+            ToyClass::Doll => write!(f, "Doll"),
+            ToyClass::Controller => write!(f, "Controller"),
+            ToyClass::Game => write!(f, "Game"),
+            ToyClass::Other(minor) => write!(f, "Toy ({minor})"),
+        }
+    }
+}
+
+/// Service class bits, which say what a device can do regardless of its major and minor class.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum ServiceClass {
+    LimitedDiscoverable,
+    LeAudio,
+    Positioning,
+    Networking,
+    Rendering,
+    Capturing,
+    ObjectTransfer,
+    Audio,
+    Telephony,
+    Information,
+}
+
+impl ServiceClass {
+    pub const ALL: [ServiceClass; 10] = [
+        ServiceClass::LimitedDiscoverable,
+        ServiceClass::LeAudio,
+        ServiceClass::Positioning,
+        ServiceClass::Networking,
+        ServiceClass::Rendering,
+        ServiceClass::Capturing,
+        ServiceClass::ObjectTransfer,
+        ServiceClass::Audio,
+        ServiceClass::Telephony,
+        ServiceClass::Information,
+    ];
+
+    pub const fn bit(self) -> u32 {
+        1 << match self {
+            ServiceClass::LimitedDiscoverable => 13,
+            ServiceClass::LeAudio => 14,
+            ServiceClass::Positioning => 16,
+            ServiceClass::Networking => 17,
+            ServiceClass::Rendering => 18,
+            ServiceClass::Capturing => 19,
+            ServiceClass::ObjectTransfer => 20,
+            ServiceClass::Audio => 21,
+            ServiceClass::Telephony => 22,
+            ServiceClass::Information => 23,
+        }
+    }
+}
+
+impl fmt::Display for ServiceClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServiceClass::LimitedDiscoverable => write!(f, "Limited Discoverable"),
+            ServiceClass::LeAudio => write!(f, "LE Audio"),
+            ServiceClass::Positioning => write!(f, "Positioning"),
+            ServiceClass::Networking => write!(f, "Networking"),
+            ServiceClass::Rendering => write!(f, "Rendering"),
+            ServiceClass::Capturing => write!(f, "Capturing"),
+            ServiceClass::ObjectTransfer => write!(f, "Object Transfer"),
+            ServiceClass::Audio => write!(f, "Audio"),
+            ServiceClass::Telephony => write!(f, "Telephony"),
+            ServiceClass::Information => write!(f, "Information"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioVideoClass, DeviceClass, MajorClass, MinorClass, PeripheralKind, ServiceClass};
+
+    #[test]
+    fn decodes_known_classes() {
+        for (raw, major, minor, services, display) in [
+            (
+                0x240404,
+                MajorClass::AudioVideo,
+                MinorClass::AudioVideo(AudioVideoClass::Headset),
+                &[ServiceClass::Rendering, ServiceClass::Audio][..],
+                "Headset",
+            ),
+            (
+                0x240418,
+                MajorClass::AudioVideo,
+                MinorClass::AudioVideo(AudioVideoClass::Headphones),
+                &[ServiceClass::Rendering, ServiceClass::Audio],
+                "Headphones",
+            ),
+            (
+                0x002580,
+                MajorClass::Peripheral,
+                MinorClass::Peripheral { keyboard: false, pointing: true, kind: PeripheralKind::Uncategorized },
+                &[ServiceClass::LimitedDiscoverable],
+                "Mouse",
+            ),
+            (
+                0x0005C8,
+                MajorClass::Peripheral,
+                MinorClass::Peripheral { keyboard: true, pointing: true, kind: PeripheralKind::Gamepad },
+                &[],
+                "Keyboard and Mouse (Gamepad)",
+            ),
+            (
+                0x5A020C,
+                MajorClass::Phone,
+                MinorClass::Phone(super::PhoneClass::Smartphone),
+                &[ServiceClass::Networking, ServiceClass::Capturing, ServiceClass::ObjectTransfer, ServiceClass::Telephony],
+                "Smartphone",
+            ),
+        ] {
+            let class = DeviceClass::from_raw(raw);
+
+            assert_eq!(class.major(), major, "{raw:#08X}");
+            assert_eq!(class.minor(), minor, "{raw:#08X}");
+            assert_eq!(class.services().collect::<Vec<_>>(), services, "{raw:#08X}");
+            assert_eq!(class.to_string(), display, "{raw:#08X}");
+        }
+    }
+
+    #[test]
+    fn displays_the_major_class_without_a_specific_minor_class() {
+        for (raw, display) in [
+            (0x000400, "Audio/Video"),
+            (0x000500, "Peripheral"),
+            (0x000600, "Imaging"),
+            (0x000900, "Health"),
+            (0x001F00, "Uncategorized"),
+            (0x001500, "Reserved (21)"),
+        ] {
+            assert_eq!(DeviceClass::from_raw(raw).to_string(), display, "{raw:#08X}");
+        }
+
+        assert_eq!(DeviceClass::from_raw(0x0006A0).to_string(), "Camera/Printer");
+        assert_eq!(format!("{:?}", DeviceClass::from_raw(0x240418)), "DeviceClass(0x240418: Headphones)");
+    }
+}
@@ -1,17 +1,30 @@
-use crate::{connect_to_device_os, remove_device, BluetoothDevice, ConnectToDeviceError, RemoveDeviceError};
+use crate::{connect_to_device_os, disconnect_device, pair_device_os, remove_device, BluetoothDevice, BluetoothError};
 
 pub trait BluetoothDeviceExt {
-    fn connect(&self) -> Result<(), ConnectToDeviceError>;
+    fn connect(&self) -> Result<(), BluetoothError>;
+
+    /// Pairs again from scratch; see [`pair_device_os`](crate::pair_device_os)
+    fn pair(&self) -> Result<(), BluetoothError>;
     
-    fn remove(&self) -> Result<(), RemoveDeviceError>;
+    fn disconnect(&self) -> Result<(), BluetoothError>;
+
+    fn remove(&self) -> Result<(), BluetoothError>;
 }
 
 impl BluetoothDeviceExt for BluetoothDevice {
-    fn connect(&self) -> Result<(), ConnectToDeviceError> {
-        connect_to_device_os(self.mac_address)
+    fn connect(&self) -> Result<(), BluetoothError> {
+        connect_to_device_os(self.mac_address, None, None)
+    }
+
+    fn pair(&self) -> Result<(), BluetoothError> {
+        pair_device_os(self.mac_address, None, None)
+    }
+
+    fn disconnect(&self) -> Result<(), BluetoothError> {
+        disconnect_device(self.mac_address, None)
     }
 
-    fn remove(&self) -> Result<(), RemoveDeviceError> {
-        remove_device(self.mac_address)
+    fn remove(&self) -> Result<(), BluetoothError> {
+        remove_device(self.mac_address, None)
     }
 }
\ No newline at end of file
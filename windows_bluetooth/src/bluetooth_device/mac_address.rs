@@ -1,9 +1,136 @@
 use std::fmt;
+use std::str::FromStr;
+#[cfg(windows)]
 use windows::Win32::Devices::Bluetooth::BLUETOOTH_ADDRESS;
 
 #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct MacAddress([u8;6]);
 
+/// Builds a [`MacAddress`] from a string literal at compile time, in any notation [`MacAddress::parse`] accepts.
+///
+/// An invalid address fails to compile.
+#[macro_export]
+macro_rules! mac {
+    ($address:literal) => {{
+        const MAC_ADDRESS: $crate::MacAddress = match $crate::MacAddress::parse($address) {
+            Ok(mac_address) => mac_address,
+            Err(_) => panic!(concat!("invalid MAC address: ", $address)),
+        };
+
+        MAC_ADDRESS
+    }};
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, thiserror::Error)]
+pub enum ParseMacAddressError {
+    #[error("MAC address has {0} characters, expected 12 hex digits, optionally grouped like AA:BB:CC:DD:EE:FF, AA-BB-CC-DD-EE-FF or AABB.CCDD.EEFF")]
+    WrongLength(usize),
+    #[error("MAC address has an invalid separator at position {0}, expected ':' or '-' between every octet, or '.' between every two")]
+    InvalidSeparator(usize),
+    #[error("MAC address has an invalid hex digit at position {0}")]
+    InvalidDigit(usize),
+}
+
+impl MacAddress {
+    /// Parses colon (`AA:BB:CC:DD:EE:FF`), dash (`AA-BB-CC-DD-EE-FF`), dot-grouped (`AABB.CCDD.EEFF`)
+    /// and bare hex (`AABBCCDDEEFF`) notation, in either case.
+    pub const fn parse(s: &str) -> Result<Self, ParseMacAddressError> {
+        let bytes = s.as_bytes();
+
+        // how many hex digits are between separators, and which separators are allowed
+        let (group_len, separators): (usize, &[u8]) = match bytes.len() {
+            12 => (12, b""),
+            14 => (4, b"."),
+            17 => (2, b":-"),
+            len => return Err(ParseMacAddressError::WrongLength(len)),
+        };
+
+        let mut octets = [0; 6];
+        let mut digits = 0;
+        let mut separator = None;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let byte = bytes[i];
+
+            // every group is followed by a separator, except the last
+            if i % (group_len + 1) == group_len {
+                let mut allowed = false;
+                let mut j = 0;
+
+                while j < separators.len() {
+                    allowed |= separators[j] == byte;
+                    j += 1;
+                }
+
+                // every separator has to be the same one
+                match separator {
+                    Some(first) if first != byte => return Err(ParseMacAddressError::InvalidSeparator(i)),
+                    _ if !allowed => return Err(ParseMacAddressError::InvalidSeparator(i)),
+                    _ => separator = Some(byte),
+                }
+            } else {
+                let digit = match byte {
+                    b'0'..=b'9' => byte - b'0',
+                    b'a'..=b'f' => byte - b'a' + 10,
+                    b'A'..=b'F' => byte - b'A' + 10,
+                    _ => return Err(ParseMacAddressError::InvalidDigit(i)),
+                };
+
+                octets[digits / 2] = octets[digits / 2] << 4 | digit;
+                digits += 1;
+            }
+
+            i += 1;
+        }
+
+        Ok(MacAddress(octets))
+    }
+}
+
+impl MacAddress {
+    /// The first three octets, which identify the vendor of universally administered addresses
+    pub const fn oui(self) -> [u8; 3] {
+        [self.0[0], self.0[1], self.0[2]]
+    }
+
+    /// Whether the address was assigned locally instead of by the vendor, which includes randomized addresses
+    pub const fn is_locally_administered(self) -> bool {
+        self.0[0] & 0b10 != 0
+    }
+
+    pub const fn is_multicast(self) -> bool {
+        self.0[0] & 0b01 != 0
+    }
+
+    /// The vendor the address was assigned to, if it's a vendor in the embedded OUI table.
+    ///
+    /// Always `None` for locally administered and multicast addresses, whose first octets don't identify a vendor.
+    pub fn vendor(self) -> Option<&'static str> {
+        if self.is_locally_administered() || self.is_multicast() {
+            return None;
+        }
+
+        super::oui::vendor_of(self.oui())
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = ParseMacAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for MacAddress {
+    type Error = ParseMacAddressError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::parse(s)
+    }
+}
+
 impl fmt::Display for MacAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::UpperHex::fmt(self, f)
@@ -52,9 +179,102 @@ impl From<MacAddress> for [u8; 6] {
     }
 }
 
+#[cfg(windows)]
 impl From<BLUETOOTH_ADDRESS> for MacAddress {
     fn from(addr: BLUETOOTH_ADDRESS) -> Self {
         // SAFETY: all bit patterns are valid for both fields of the union
         Self(unsafe { addr.Anonymous.rgBytes })
     }
-}
\ No newline at end of file
+}
+
+/// Serialized in its string form
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::test_devices::HEADPHONES;
+    use super::{MacAddress, ParseMacAddressError};
+
+    #[test]
+    fn parses_every_notation() {
+        assert_eq!(HEADPHONES, MacAddress([0x00, 0x1A, 0x7D, 0xDA, 0x71, 0x13]));
+
+        for notation in [
+            "00:1A:7D:DA:71:13",
+            "00-1a-7d-da-71-13",
+            "001A.7DDA.7113",
+            "001a7dDA7113",
+        ] {
+            assert_eq!(MacAddress::parse(notation), Ok(HEADPHONES), "{notation}");
+        }
+
+        assert_eq!(mac!("00:1a:7d:DA:71:13"), HEADPHONES);
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        for (invalid, err) in [
+            ("00:1A:7D-DA:71:13", ParseMacAddressError::InvalidSeparator(8)),
+            ("00-1A:7D:DA:71:13", ParseMacAddressError::InvalidSeparator(5)),
+            ("00.1A.7D.DA.71.13", ParseMacAddressError::InvalidSeparator(2)),
+            ("001A:7DDA:7113", ParseMacAddressError::InvalidSeparator(4)),
+            ("001A7.DDA7.113", ParseMacAddressError::InvalidSeparator(4)),
+            ("00:1A:7D:DA:71:1", ParseMacAddressError::WrongLength(16)),
+            ("00:1A:7D:DA:71:13:", ParseMacAddressError::WrongLength(18)),
+            ("", ParseMacAddressError::WrongLength(0)),
+            ("00:1A:7D:DA:71:1G", ParseMacAddressError::InvalidDigit(16)),
+            ("+01A7DDA7113", ParseMacAddressError::InvalidDigit(0)),
+            ("00 1A 7D DA 71 13", ParseMacAddressError::InvalidSeparator(2)),
+            ("0é1A7DDA711", ParseMacAddressError::InvalidDigit(1)),
+        ] {
+            assert_eq!(MacAddress::parse(invalid), Err(err), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn displays_in_colon_notation() {
+        assert_eq!(HEADPHONES.to_string(), "00:1A:7D:DA:71:13");
+        assert_eq!(format!("{HEADPHONES:x}"), "00:1a:7d:da:71:13");
+        assert_eq!(HEADPHONES.to_string().parse(), Ok(HEADPHONES));
+    }
+
+    #[test]
+    fn vendors_only_of_universal_addresses() {
+        let mouse = mac!("34:88:5D:10:2B:C4");
+        assert_eq!(mouse.vendor(), Some("Logitech"));
+        assert!(!mouse.is_locally_administered() && !mouse.is_multicast());
+
+        // the same OUI with the locally administered or multicast bit set isn't the vendor's
+        let random = mac!("36:88:5D:10:2B:C4");
+        assert!(random.is_locally_administered());
+        assert_eq!(random.vendor(), None);
+
+        let multicast = mac!("35:88:5D:10:2B:C4");
+        assert!(multicast.is_multicast());
+        assert_eq!(multicast.vendor(), None);
+
+        assert_eq!(mac!("7A:21:9C:44:E0:05").vendor(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let json = serde_json::to_string(&HEADPHONES).unwrap();
+
+        assert_eq!(json, r#""00:1A:7D:DA:71:13""#);
+        assert_eq!(serde_json::from_str::<MacAddress>(&json).unwrap(), HEADPHONES);
+        assert!(serde_json::from_str::<MacAddress>(r#""00:1A:7D""#).is_err());
+    }
+}
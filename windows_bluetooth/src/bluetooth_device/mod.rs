@@ -1,60 +1,233 @@
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use windows::Win32::Devices::Bluetooth::BLUETOOTH_DEVICE_INFO;
-use windows::Win32::Foundation::SYSTEMTIME;
+use std::borrow::Cow;
+use chrono::NaiveDateTime;
 
+mod class;
 mod mac_address;
+mod oui;
+mod profile;
+#[cfg(windows)]
 mod ext;
+#[cfg(windows)]
+mod win32;
 
+#[cfg(windows)]
 pub use ext::BluetoothDeviceExt;
+pub use class::*;
 pub use mac_address::MacAddress;
+pub use profile::*;
 
 
-// TODO: don't make fields public; getter?
-#[derive(Debug, Clone)]
+/// A device known to a radio, as of the scan or lookup which returned it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct BluetoothDevice {
-    pub mac_address: MacAddress,
-    pub name: Option<String>,
-    pub class: u32,
-    pub connected: bool,
-    pub remembered: bool,
-    pub authenticated: bool,
-    pub last_seen: Option<NaiveDateTime>,
-    pub last_used: Option<NaiveDateTime>,
+    pub(crate) mac_address: MacAddress,
+    pub(crate) name: Option<String>,
+    pub(crate) class: DeviceClass,
+    pub(crate) connected: bool,
+    pub(crate) remembered: bool,
+    pub(crate) authenticated: bool,
+    pub(crate) last_seen: Option<NaiveDateTime>,
+    pub(crate) last_used: Option<NaiveDateTime>,
+    /// UUIDs of the services the device has installed or advertises
+    pub(crate) services: Vec<ServiceUuid>,
+}
+
+/// An attribute which differs between two snapshots of the same device, see [`BluetoothDevice::diff`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeviceChange {
+    Name { old: Option<String>, new: Option<String> },
+    Class { old: DeviceClass, new: DeviceClass },
+    /// Whether the device is connected now
+    Connected(bool),
+    /// Whether the device is remembered now
+    Remembered(bool),
+    /// Whether the device is paired now
+    Authenticated(bool),
+    LastSeen { old: Option<NaiveDateTime>, new: Option<NaiveDateTime> },
+    LastUsed { old: Option<NaiveDateTime>, new: Option<NaiveDateTime> },
+    Services { added: Vec<ServiceUuid>, removed: Vec<ServiceUuid> },
 }
 
 impl BluetoothDevice {
-    pub(crate) unsafe fn from_win32_bluetooth_device(device: &BLUETOOTH_DEVICE_INFO) -> Self {
-        let name = OsString::from_wide(&device.szName)
-            .to_string_lossy()
-            .trim_end_matches('\0')
-            .to_owned();
-        
-        Self {
-            // SAFETY: all bit patterns are valid for both fields of the union
-            mac_address: MacAddress::from(device.Address),
-            name: (!name.is_empty()).then_some(name),
-            class: device.ulClassofDevice,
-            connected: device.fConnected.as_bool() && device.fRemembered.as_bool(),
-            remembered: device.fRemembered.as_bool(),
-            authenticated: device.fAuthenticated.as_bool(),
-            last_seen: into_opt_naive_date(device.stLastSeen),
-            last_used: into_opt_naive_date(device.stLastUsed),
+    pub fn mac_address(&self) -> MacAddress {
+        self.mac_address
+    }
+
+    /// The name the device advertises, see [`display_name`](Self::display_name) for one to show
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn class(&self) -> DeviceClass {
+        self.class
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Whether the OS remembers the device, which paired devices always are
+    pub fn is_remembered(&self) -> bool {
+        self.remembered
+    }
+
+    /// Whether the device is paired
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    pub fn last_seen(&self) -> Option<NaiveDateTime> {
+        self.last_seen
+    }
+
+    pub fn last_used(&self) -> Option<NaiveDateTime> {
+        self.last_used
+    }
+
+    /// UUIDs of the services the device has installed or advertises
+    pub fn services(&self) -> &[ServiceUuid] {
+        &self.services
+    }
+
+    /// What changed from this snapshot of a device to a `newer` one, in the order of the device's fields.
+    ///
+    /// The MAC addresses aren't compared, since both snapshots are expected to be of the same device.
+    pub fn diff(&self, newer: &BluetoothDevice) -> Vec<DeviceChange> {
+        let mut changes = Vec::new();
+
+        if self.name != newer.name {
+            changes.push(DeviceChange::Name { old: self.name.clone(), new: newer.name.clone() });
+        }
+
+        if self.class != newer.class {
+            changes.push(DeviceChange::Class { old: self.class, new: newer.class });
+        }
+
+        if self.connected != newer.connected {
+            changes.push(DeviceChange::Connected(newer.connected));
+        }
+
+        if self.remembered != newer.remembered {
+            changes.push(DeviceChange::Remembered(newer.remembered));
+        }
+
+        if self.authenticated != newer.authenticated {
+            changes.push(DeviceChange::Authenticated(newer.authenticated));
+        }
+
+        if self.last_seen != newer.last_seen {
+            changes.push(DeviceChange::LastSeen { old: self.last_seen, new: newer.last_seen });
+        }
+
+        if self.last_used != newer.last_used {
+            changes.push(DeviceChange::LastUsed { old: self.last_used, new: newer.last_used });
+        }
+
+        let added = newer.services.iter().filter(|uuid| !self.services.contains(uuid)).copied().collect::<Vec<_>>();
+        let removed = self.services.iter().filter(|uuid| !newer.services.contains(uuid)).copied().collect::<Vec<_>>();
+
+        if !added.is_empty() || !removed.is_empty() {
+            changes.push(DeviceChange::Services { added, removed });
         }
+
+        changes
+    }
+
+    /// The known profiles among the device's services
+    pub fn profiles(&self) -> impl Iterator<Item = Profile> + '_ {
+        self.services.iter().filter_map(|uuid| uuid.profile())
+    }
+
+    /// The device's name, or its vendor like "Sony device" if it doesn't have one
+    pub fn display_name(&self) -> Option<Cow<'_, str>> {
+        match (&self.name, self.mac_address.vendor()) {
+            (Some(name), _) => Some(Cow::Borrowed(name)),
+            (None, Some(vendor)) => Some(Cow::Owned(format!("{vendor} device"))),
+            (None, None) => None,
+        }
+    }
+
+    pub fn has_category(&self, category: ProfileCategory) -> bool {
+        self.profiles().any(|profile| profile.category() == category)
     }
 }
 
-fn into_opt_naive_date(system_time: SYSTEMTIME) -> Option<NaiveDateTime> {
-    const EPOCH: SYSTEMTIME = SYSTEMTIME { wYear: 1601, wMonth: 1, wDayOfWeek: 1, wDay: 1, wHour: 0, wMinute: 0, wSecond: 0, wMilliseconds: 0 };
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+    use crate::backend::test_devices::headphones;
+    use super::{DeviceChange, DeviceClass, Profile};
+
+    fn day(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 6, day).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn same_device_has_no_changes() {
+        let device = headphones().service(Profile::A2dpSink).last_seen(day(1)).build();
+
+        assert_eq!(device.diff(&device.clone()), []);
+    }
 
-    match system_time {
-        EPOCH => None,
-        st => Some(NaiveDateTime::new(
-            NaiveDate::from_ymd_opt(st.wYear as _, st.wMonth as _, st.wDay as _)
-                .expect("should be valid date"),
-            NaiveTime::from_hms_milli_opt(st.wHour as _, st.wMinute as _, st.wSecond as _, st.wMilliseconds as _)
-                .expect("should be valid date"),
-        ))
+    #[test]
+    fn changes_are_in_field_order() {
+        let old = headphones().last_seen(day(1)).build();
+        let new = headphones()
+            .name("Work Headphones")
+            .class(0x240404)
+            .connected(true)
+            .remembered(false)
+            .authenticated(false)
+            .last_seen(day(2))
+            .last_used(day(2))
+            .service(Profile::A2dpSink)
+            .build();
+
+        assert_eq!(old.diff(&new), [
+            DeviceChange::Name { old: Some("WH-1000XM4".to_string()), new: Some("Work Headphones".to_string()) },
+            DeviceChange::Class { old: DeviceClass::from_raw(0x240418), new: DeviceClass::from_raw(0x240404) },
+            DeviceChange::Connected(true),
+            DeviceChange::Remembered(false),
+            DeviceChange::Authenticated(false),
+            DeviceChange::LastSeen { old: Some(day(1)), new: Some(day(2)) },
+            DeviceChange::LastUsed { old: None, new: Some(day(2)) },
+            DeviceChange::Services { added: vec![Profile::A2dpSink.uuid()], removed: Vec::new() },
+        ]);
+
+        assert_eq!(new.diff(&old)[2..5], [
+            DeviceChange::Connected(false),
+            DeviceChange::Remembered(true),
+            DeviceChange::Authenticated(true),
+        ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn services_changes_leave_out_kept_services() {
+        let old = headphones().service(Profile::A2dpSink).service(Profile::AvrcpTarget).service(Profile::Handsfree).build();
+        let new = headphones().service(Profile::Hid).service(Profile::AvrcpTarget).service(Profile::A2dpSink).build();
+
+        assert_eq!(old.diff(&new), [DeviceChange::Services {
+            added: vec![Profile::Hid.uuid()],
+            removed: vec![Profile::Handsfree.uuid()],
+        }]);
+
+        // reordered services are the same services
+        let reordered = headphones().service(Profile::Handsfree).service(Profile::A2dpSink).service(Profile::AvrcpTarget).build();
+        assert_eq!(old.diff(&reordered), []);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let device = headphones().connected(true).last_used(day(3)).service(Profile::A2dpSink).build();
+
+        let json = serde_json::to_string(&device).unwrap();
+
+        assert!(json.contains(r#""mac_address":"00:1A:7D:DA:71:13""#), "{json}");
+        assert!(json.contains(r#""services":["0000110b-0000-1000-8000-00805f9b34fb"]"#), "{json}");
+        assert_eq!(serde_json::from_str::<super::BluetoothDevice>(&json).unwrap(), device);
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::OnceLock;
+
+/// Compact table of vendor names and their OUIs, see the header of the file for its format
+const OUI_TABLE: &str = include_str!("oui.txt");
+
+/// Looks up the vendor an OUI, the first three octets of a MAC address, was assigned to.
+pub(crate) fn vendor_of(oui: [u8; 3]) -> Option<&'static str> {
+    static VENDORS: OnceLock<Vec<([u8; 3], &'static str)>> = OnceLock::new();
+
+    let vendors = VENDORS.get_or_init(|| {
+        let mut vendors = OUI_TABLE.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('\t'))
+            .flat_map(|(vendor, ouis)| ouis.split(' ').filter_map(move |oui| Some((parse_oui(oui)?, vendor))))
+            .collect::<Vec<_>>();
+
+        vendors.sort_unstable_by_key(|&(oui, _)| oui);
+        vendors
+    });
+
+    vendors.binary_search_by_key(&oui, |&(oui, _)| oui)
+        .ok()
+        .map(|i| vendors[i].1)
+}
+
+/// Parses an OUI written as exactly six hex digits, like `34885D`
+fn parse_oui(oui: &str) -> Option<[u8; 3]> {
+    if oui.len() != 6 || !oui.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let [_, a, b, c] = u32::from_str_radix(oui, 16).ok()?.to_be_bytes();
+
+    Some([a, b, c])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{parse_oui, vendor_of, OUI_TABLE};
+
+    #[test]
+    fn parses_only_six_hex_digits() {
+        assert_eq!(parse_oui("34885D"), Some([0x34, 0x88, 0x5D]));
+        assert_eq!(parse_oui("34885d"), Some([0x34, 0x88, 0x5D]));
+
+        for invalid in ["", "34885", "34885D0", "0034885D", "+4885D", "34885G", "3488 5D"] {
+            assert_eq!(parse_oui(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn every_table_entry_parses_once() {
+        let mut vendors = HashMap::new();
+
+        for line in OUI_TABLE.lines().filter(|line| !line.starts_with('#')) {
+            let (vendor, ouis) = line.split_once('\t').unwrap_or_else(|| panic!("no tab in {line:?}"));
+
+            for oui in ouis.split(' ') {
+                let parsed = parse_oui(oui).unwrap_or_else(|| panic!("invalid OUI {oui:?} of {vendor}"));
+
+                if let Some(other) = vendors.insert(parsed, vendor) {
+                    panic!("{oui} is listed for both {other} and {vendor}");
+                }
+            }
+        }
+
+        assert!(!vendors.is_empty());
+    }
+
+    #[test]
+    fn looks_up_vendors() {
+        assert_eq!(vendor_of([0x34, 0x88, 0x5D]), Some("Logitech"));
+        assert_eq!(vendor_of([0x00, 0x03, 0x93]), Some("Apple"));
+        assert_eq!(vendor_of([0x00, 0x00, 0x01]), None);
+    }
+}
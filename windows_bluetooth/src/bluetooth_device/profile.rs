@@ -0,0 +1,283 @@
+use std::fmt;
+use std::str::FromStr;
+#[cfg(windows)]
+use windows::core::GUID;
+
+/// The Bluetooth base UUID, which 16-bit assigned numbers are offsets into
+const BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805F9B34FB;
+
+/// UUID of a service a device advertises or has installed, such as a profile.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServiceUuid(u128);
+
+impl ServiceUuid {
+    pub const fn from_u128(uuid: u128) -> Self {
+        Self(uuid)
+    }
+
+    /// The full UUID of a 16-bit assigned number, like `0x110B` for A2DP sinks
+    pub const fn from_short(short: u16) -> Self {
+        Self(BASE_UUID | (short as u128) << 96)
+    }
+
+    pub const fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// The 16-bit assigned number, if this is one
+    pub const fn short(self) -> Option<u16> {
+        let short = (self.0 >> 96) as u16;
+
+        match Self::from_short(short).0 == self.0 {
+            true => Some(short),
+            false => None,
+        }
+    }
+
+    /// The known profile this is the UUID of
+    pub fn profile(self) -> Option<Profile> {
+        Profile::from_uuid(self)
+    }
+}
+
+impl fmt::Display for ServiceUuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            self.0 >> 96,
+            (self.0 >> 80) as u16,
+            (self.0 >> 64) as u16,
+            (self.0 >> 48) as u16,
+            self.0 & 0xFFFF_FFFF_FFFF,
+        )
+    }
+}
+
+impl fmt::Debug for ServiceUuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid service UUID {0:?}, expected the form 0000110b-0000-1000-8000-00805f9b34fb")]
+pub struct ParseServiceUuidError(String);
+
+impl FromStr for ServiceUuid {
+    type Err = ParseServiceUuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups = s.split('-').map(str::len).collect::<Vec<_>>();
+
+        // from_str_radix alone would also take a sign, e.g. a leading '+'
+        if groups != [8, 4, 4, 4, 12] || !s.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
+            return Err(ParseServiceUuidError(s.to_owned()));
+        }
+
+        u128::from_str_radix(&s.replace('-', ""), 16)
+            .map(Self)
+            .map_err(|_| ParseServiceUuidError(s.to_owned()))
+    }
+}
+
+impl From<u128> for ServiceUuid {
+    fn from(uuid: u128) -> Self {
+        Self(uuid)
+    }
+}
+
+#[cfg(windows)]
+impl From<GUID> for ServiceUuid {
+    fn from(guid: GUID) -> Self {
+        Self(guid.to_u128())
+    }
+}
+
+#[cfg(windows)]
+impl From<ServiceUuid> for GUID {
+    fn from(uuid: ServiceUuid) -> Self {
+        GUID::from_u128(uuid.0)
+    }
+}
+
+/// What a profile is used for, e.g. to treat headphones differently from a keyboard.
+#[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ProfileCategory {
+    /// Plays audio from this computer, like headphones or a speaker
+    AudioSink,
+    /// Sends audio to this computer, like a phone
+    AudioSource,
+    /// Phone calls, which includes the microphone of most headsets
+    Calls,
+    /// Media controls, such as play and pause
+    RemoteControl,
+    /// Keyboards, mice and game controllers
+    Input,
+    /// Serial ports, used by all sorts of devices
+    Serial,
+}
+
+impl fmt::Display for ProfileCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ProfileCategory::AudioSink => "Audio Sink",
+            ProfileCategory::AudioSource => "Audio Source",
+            ProfileCategory::Calls => "Calls",
+            ProfileCategory::RemoteControl => "Remote Control",
+            ProfileCategory::Input => "Input",
+            ProfileCategory::Serial => "Serial",
+        })
+    }
+}
+
+/// Profiles this crate knows the UUIDs of; other services are left as their [`ServiceUuid`].
+#[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Profile {
+    SerialPort,
+    Headset,
+    A2dpSource,
+    A2dpSink,
+    AvrcpTarget,
+    Avrcp,
+    AvrcpController,
+    HeadsetAudioGateway,
+    Handsfree,
+    HandsfreeAudioGateway,
+    Hid,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 11] = [
+        Profile::SerialPort,
+        Profile::Headset,
+        Profile::A2dpSource,
+        Profile::A2dpSink,
+        Profile::AvrcpTarget,
+        Profile::Avrcp,
+        Profile::AvrcpController,
+        Profile::HeadsetAudioGateway,
+        Profile::Handsfree,
+        Profile::HandsfreeAudioGateway,
+        Profile::Hid,
+    ];
+
+    pub const fn uuid(self) -> ServiceUuid {
+        ServiceUuid::from_short(match self {
+            Profile::SerialPort => 0x1101,
+            Profile::Headset => 0x1108,
+            Profile::A2dpSource => 0x110A,
+            Profile::A2dpSink => 0x110B,
+            Profile::AvrcpTarget => 0x110C,
+            Profile::Avrcp => 0x110E,
+            Profile::AvrcpController => 0x110F,
+            Profile::HeadsetAudioGateway => 0x1112,
+            Profile::Handsfree => 0x111E,
+            Profile::HandsfreeAudioGateway => 0x111F,
+            Profile::Hid => 0x1124,
+        })
+    }
+
+    pub fn from_uuid(uuid: ServiceUuid) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.uuid() == uuid)
+    }
+
+    /// The profile's abbreviation, like "A2DP"
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Profile::SerialPort => "SPP",
+            Profile::Headset | Profile::HeadsetAudioGateway => "HSP",
+            Profile::A2dpSource | Profile::A2dpSink => "A2DP",
+            Profile::AvrcpTarget | Profile::Avrcp | Profile::AvrcpController => "AVRCP",
+            Profile::Handsfree | Profile::HandsfreeAudioGateway => "HFP",
+            Profile::Hid => "HID",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::SerialPort => "Serial Port",
+            Profile::Headset => "Headset",
+            Profile::A2dpSource => "Audio Source",
+            Profile::A2dpSink => "Audio Sink",
+            Profile::AvrcpTarget => "Remote Control Target",
+            Profile::Avrcp => "Remote Control",
+            Profile::AvrcpController => "Remote Control Controller",
+            Profile::HeadsetAudioGateway => "Headset Audio Gateway",
+            Profile::Handsfree => "Handsfree",
+            Profile::HandsfreeAudioGateway => "Handsfree Audio Gateway",
+            Profile::Hid => "Human Interface Device",
+        }
+    }
+
+    pub fn category(self) -> ProfileCategory {
+        match self {
+            Profile::SerialPort => ProfileCategory::Serial,
+            Profile::A2dpSink => ProfileCategory::AudioSink,
+            Profile::A2dpSource => ProfileCategory::AudioSource,
+            Profile::Headset
+            | Profile::HeadsetAudioGateway
+            | Profile::Handsfree
+            | Profile::HandsfreeAudioGateway => ProfileCategory::Calls,
+            Profile::AvrcpTarget | Profile::Avrcp | Profile::AvrcpController => ProfileCategory::RemoteControl,
+            Profile::Hid => ProfileCategory::Input,
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<Profile> for ServiceUuid {
+    fn from(profile: Profile) -> Self {
+        profile.uuid()
+    }
+}
+
+/// Serialized in its string form
+#[cfg(feature = "serde")]
+impl serde::Serialize for ServiceUuid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ServiceUuid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceUuid;
+
+    #[test]
+    fn parses_only_hex_uuids() {
+        assert_eq!("0000110b-0000-1000-8000-00805f9b34fb".parse(), Ok(ServiceUuid::from_short(0x110B)));
+        assert_eq!("0000110B-0000-1000-8000-00805F9B34FB".parse(), Ok(ServiceUuid::from_short(0x110B)));
+
+        for invalid in [
+            "+000110b-0000-1000-8000-00805f9b34fb",
+            "0000110b-0000-1000-8000-+0805f9b34fb",
+            "0000110g-0000-1000-8000-00805f9b34fb",
+            "0000110b00001000800000805f9b34fb",
+            "0000110b-0000-1000-8000-00805f9b34f",
+            "",
+        ] {
+            assert!(invalid.parse::<ServiceUuid>().is_err(), "{invalid:?} shouldn't parse");
+        }
+    }
+
+    #[test]
+    fn displays_in_the_form_it_parses() {
+        let uuid = ServiceUuid::from_u128(0x12345678_9abc_def0_1234_56789abcdef0);
+
+        assert_eq!(uuid.to_string(), "12345678-9abc-def0-1234-56789abcdef0");
+        assert_eq!(uuid.to_string().parse(), Ok(uuid));
+    }
+}
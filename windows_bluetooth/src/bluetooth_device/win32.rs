@@ -0,0 +1,51 @@
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use windows::Win32::Devices::Bluetooth::BLUETOOTH_DEVICE_INFO;
+use windows::Win32::Foundation::SYSTEMTIME;
+use crate::with_err;
+use super::{BluetoothDevice, DeviceClass, MacAddress, ServiceUuid};
+
+impl BluetoothDevice {
+    pub(crate) unsafe fn from_win32_bluetooth_device(device: &BLUETOOTH_DEVICE_INFO) -> Self {
+        let name = OsString::from_wide(&device.szName)
+            .to_string_lossy()
+            .trim_end_matches('\0')
+            .to_owned();
+
+        // services are only installed for remembered devices, and not knowing them shouldn't fail the whole scan
+        // SAFETY: device info should be correctly formed
+        let services = unsafe { with_err::bluetooth_enumerate_installed_services(None, device) }
+            .map(|guids| guids.into_iter().map(ServiceUuid::from).collect())
+            .unwrap_or_default();
+
+        Self {
+            // SAFETY: all bit patterns are valid for both fields of the union
+            mac_address: MacAddress::from(device.Address),
+            name: (!name.is_empty()).then_some(name),
+            class: DeviceClass::from_raw(device.ulClassofDevice),
+            connected: device.fConnected.as_bool() && device.fRemembered.as_bool(),
+            remembered: device.fRemembered.as_bool(),
+            authenticated: device.fAuthenticated.as_bool(),
+            last_seen: into_opt_naive_date(device.stLastSeen),
+            last_used: into_opt_naive_date(device.stLastUsed),
+            services,
+        }
+    }
+}
+
+fn into_opt_naive_date(system_time: SYSTEMTIME) -> Option<NaiveDateTime> {
+    const EPOCH: SYSTEMTIME = SYSTEMTIME { wYear: 1601, wMonth: 1, wDayOfWeek: 1, wDay: 1, wHour: 0, wMinute: 0, wSecond: 0, wMilliseconds: 0 };
+
+    if system_time == EPOCH {
+        return None;
+    }
+
+    let st = system_time;
+
+    // a date the OS got wrong is treated as unknown
+    Some(NaiveDateTime::new(
+        NaiveDate::from_ymd_opt(st.wYear as _, st.wMonth as _, st.wDay as _)?,
+        NaiveTime::from_hms_milli_opt(st.wHour as _, st.wMinute as _, st.wSecond as _, st.wMilliseconds as _)?,
+    ))
+}
@@ -1,28 +1,63 @@
-use windows::core::HRESULT;
-use windows::Win32::Devices::Bluetooth::{BLUETOOTH_MITM_ProtectionNotRequired, BluetoothAuthenticateDeviceEx, AUTHENTICATION_REQUIREMENTS, BLUETOOTH_ADDRESS, BLUETOOTH_ADDRESS_0, BLUETOOTH_DEVICE_INFO};
-use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_CANCELLED, ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_NOT_AUTHENTICATED, ERROR_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_REVISION_MISMATCH, ERROR_SUCCESS};
-use windows::core::Error as WinErr;
+use std::ffi::c_void;
+use windows::core::{BOOL, HRESULT};
+use windows::Win32::Devices::Bluetooth::{BLUETOOTH_MITM_ProtectionNotRequired, BluetoothAuthenticateDeviceEx, BluetoothSendAuthenticationResponseEx, BluetoothUnregisterAuthentication, AUTHENTICATION_REQUIREMENTS, BLUETOOTH_ADDRESS, BLUETOOTH_ADDRESS_0, BLUETOOTH_AUTHENTICATE_RESPONSE, BLUETOOTH_AUTHENTICATION_CALLBACK_PARAMS, BLUETOOTH_AUTHENTICATION_METHOD_LEGACY, BLUETOOTH_AUTHENTICATION_METHOD_NUMERIC_COMPARISON, BLUETOOTH_AUTHENTICATION_METHOD_PASSKEY, BLUETOOTH_AUTHENTICATION_METHOD_PASSKEY_NOTIFICATION, BLUETOOTH_DEVICE_INFO, BLUETOOTH_NUMERIC_COMPARISON_INFO, BLUETOOTH_PASSKEY_INFO, BLUETOOTH_PIN_INFO, BLUETOOTH_SERVICE_DISABLE, BLUETOOTH_SERVICE_ENABLE};
+use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_CANCELLED, ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_NOT_AUTHENTICATED, ERROR_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SUCCESS, HANDLE};
 use crate::bluetooth_device::MacAddress;
-use crate::err_eq;
-use crate::with_err::{self, BluetoothGetDeviceInfoErr, BluetoothRemoveDeviceErr};
-
-#[derive(Debug, thiserror::Error)]
-pub enum ConnectToDeviceError {
-    #[error("The provided MAC Address was invalid")]
-    InvalidMacAddress,
-    #[error("Error with bluetooth adapter, bluetooth may be off, or no bluetooth adapter exists.")]
-    BluetoothError,
-    #[error("The user wasn't authenticated or canceled the operation")]
-    Cancelled,
-    #[error("Insufficient permissions or bluetooth device cannot be paired to")]
-    AccessDenied,
-    #[error("Unhandled error from Windows api: {0}")]
-    UnhandledWin32ApiErr(WinErr),
+use crate::{err_eq, open_radio, open_radio_or_first, BluetoothError, ErrorKind, Failure, Operation, PairingAgent, Radio, RadioHandle};
+use crate::with_err::{self, Win32Err};
+
+/// Connects to a device through `radio`, or any radio if `None`, reconnecting without pairing again if it's already paired.
+///
+/// If it has to pair, `agent` answers the device's requests, otherwise Windows shows its own pairing prompts.
+pub fn connect_to_device_os(mac_address: MacAddress, radio: Option<&Radio>, agent: Option<&dyn PairingAgent>) -> Result<(), BluetoothError> {
+    connect(mac_address, radio, agent).map_err(|err| err.during(Operation::Connect, Some(mac_address)))
+}
+
+/// Pairs with a device from scratch, removing its existing pairing first, which requires the device to be in pairing mode.
+///
+/// Only needed when the existing pairing is broken, e.g. after the device was reset; otherwise use [`connect_to_device_os`].
+pub fn pair_device_os(mac_address: MacAddress, radio: Option<&Radio>, agent: Option<&dyn PairingAgent>) -> Result<(), BluetoothError> {
+    pair(mac_address, radio, agent).map_err(|err| err.during(Operation::Connect, Some(mac_address)))
 }
 
-// TODO: if the device is paired but isn't connected, we have to remove it first before connecting, which requires the device to be in pairing mode; fix this
-pub fn connect_to_device_os(mac_address: MacAddress) -> Result<(), ConnectToDeviceError> {
-    let mut device_info = BLUETOOTH_DEVICE_INFO {
+fn connect(mac_address: MacAddress, radio: Option<&Radio>, agent: Option<&dyn PairingAgent>) -> Result<(), Failure> {
+    let h_radio = open_radio(radio)?;
+    let mut device_info = device_info_of(mac_address);
+
+    // SAFETY: device info is properly initialized, not being used concurrently
+    unsafe { with_err::bluetooth_get_device_info(h_radio.as_ref().map(RadioHandle::handle), &mut device_info)?; }
+
+    if device_info.fConnected.as_bool() {
+        return Ok(());
+    }
+
+    if device_info.fAuthenticated.as_bool() {
+        reconnect(&device_info, h_radio)
+    } else {
+        authenticate(&mut device_info, h_radio, agent)
+    }
+}
+
+fn pair(mac_address: MacAddress, radio: Option<&Radio>, agent: Option<&dyn PairingAgent>) -> Result<(), Failure> {
+    let h_radio = open_radio(radio)?;
+    let mut device_info = device_info_of(mac_address);
+
+    // SAFETY: device info is properly initialized, not being used concurrently
+    unsafe { with_err::bluetooth_get_device_info(h_radio.as_ref().map(RadioHandle::handle), &mut device_info)?; }
+
+    if device_info.fRemembered.as_bool() {
+        // SAFETY: not being used concurrently
+        unsafe { with_err::bluetooth_remove_device(&device_info.Address)?; }
+
+        // SAFETY: device info's state was given by OS, so assumed to be safe; not being concurrently modified
+        unsafe { with_err::bluetooth_get_device_info(h_radio.as_ref().map(RadioHandle::handle), &mut device_info)?; }
+    }
+
+    authenticate(&mut device_info, h_radio, agent)
+}
+
+pub(crate) fn device_info_of(mac_address: MacAddress) -> BLUETOOTH_DEVICE_INFO {
+    BLUETOOTH_DEVICE_INFO {
         dwSize: size_of::<BLUETOOTH_DEVICE_INFO>() as _,
         Address: BLUETOOTH_ADDRESS {
             Anonymous: {
@@ -30,58 +65,157 @@ pub fn connect_to_device_os(mac_address: MacAddress) -> Result<(), ConnectToDevi
             },
         },
         .. unsafe { std::mem::zeroed() } // SAFETY: all of BLUETOOTH_DEVICE_INFO's fields can and should be zeroed
+    }
+}
+
+/// Reconnects to a paired device by toggling its installed services, which makes Windows connect their profiles again.
+fn reconnect(device_info: &BLUETOOTH_DEVICE_INFO, h_radio: Option<RadioHandle>) -> Result<(), Failure> {
+    // changing service state needs a radio handle, even when any radio would do
+    let h_radio = match h_radio {
+        Some(h_radio) => h_radio,
+        None => open_radio_or_first(None)?,
     };
 
-    // SAFETY: device info is properly initialized, not being used concurrently
-    unsafe { with_err::bluetooth_get_device_info(None, &mut device_info)?; }
+    // SAFETY: radio handle is open, device info's state was given by OS
+    let services = unsafe { with_err::bluetooth_enumerate_installed_services(Some(h_radio.handle()), device_info)? };
 
-    if device_info.fConnected.as_bool() {
-        return Ok(());
+    // toggling nothing would report success without reconnecting, which happens when pairing broke halfway
+    if services.is_empty() {
+        return Err(Failure::new(ErrorKind::NoServices));
     }
-    
-    if device_info.fAuthenticated.as_bool() {
-        // SAFETY: not being used concurrently
-        unsafe { with_err::bluetooth_remove_device(&device_info.Address)?; }
 
-        // SAFETY: device info's state was given by OS, so assumed to be safe; not being concurrently modified
-        unsafe { with_err::bluetooth_get_device_info(None, &mut device_info)?; }
+    for service in &services {
+        for flags in [BLUETOOTH_SERVICE_DISABLE, BLUETOOTH_SERVICE_ENABLE] {
+            // SAFETY: radio handle is open, device info's state was given by OS
+            match unsafe { with_err::bluetooth_set_service_state(h_radio.handle(), device_info, service, flags) } {
+                // installed services can still be missing from the device's advertised ones, there's nothing to reconnect then
+                Ok(()) => {}
+                Err(err) if err.is(ERROR_SERVICE_DOES_NOT_EXIST) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
     }
 
+    Ok(())
+}
+
+fn authenticate(device_info: &mut BLUETOOTH_DEVICE_INFO, h_radio: Option<RadioHandle>, agent: Option<&dyn PairingAgent>) -> Result<(), Failure> {
+    let registration = agent
+        .map(|agent| AuthRegistration::register(device_info, h_radio.as_ref().map(RadioHandle::handle), agent))
+        .transpose()?;
+
     // SAFETY: device info's state was given by OS, so assumed to be safe; not being concurrently modified
-    let win32err = unsafe { BluetoothAuthenticateDeviceEx(None, None, &mut device_info, None, AUTHENTICATION_REQUIREMENTS(BLUETOOTH_MITM_ProtectionNotRequired.0)) };
+    let win32err = unsafe { BluetoothAuthenticateDeviceEx(None, h_radio.as_ref().map(RadioHandle::handle), device_info, None, AUTHENTICATION_REQUIREMENTS(BLUETOOTH_MITM_ProtectionNotRequired.0)) };
+    let h_res = HRESULT::from_win32(win32err);
+
+    // stop answering before the radio handle the callback responds through can be closed
+    drop(registration);
 
     // invalid parameter if null, wait timeout, access denied
-    match HRESULT::from_win32(win32err) {
-        res if err_eq(res, ERROR_REVISION_MISMATCH) => unreachable!("dwSize should be correct"),
-        res if err_eq(res, ERROR_NO_MORE_ITEMS) => unreachable!("device should've been removed if authenticated"),
-        res if err_eq(res, ERROR_INVALID_PARAMETER) => Err(ConnectToDeviceError::InvalidMacAddress),
-        res if err_eq(res, ERROR_NOT_FOUND) => Err(ConnectToDeviceError::InvalidMacAddress),
-        res if err_eq(res, ERROR_INVALID_HANDLE) => Err(ConnectToDeviceError::BluetoothError),
-        res if err_eq(res, ERROR_GEN_FAILURE) => Err(ConnectToDeviceError::BluetoothError),
-        res if err_eq(res, ERROR_CANCELLED) => Err(ConnectToDeviceError::Cancelled),
-        res if err_eq(res, ERROR_NOT_AUTHENTICATED) => Err(ConnectToDeviceError::Cancelled),
-        res if err_eq(res, ERROR_ACCESS_DENIED) => Err(ConnectToDeviceError::AccessDenied),
-        res if err_eq(res, ERROR_SUCCESS) => Ok(()), // successfully got device info,
-        res => Err(ConnectToDeviceError::UnhandledWin32ApiErr(WinErr::from_hresult(res))),
+    let kind = match h_res {
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(()), // successfully got device info,
+        // paired in the meantime, e.g. through Windows settings
+        res if err_eq(res, ERROR_NO_MORE_ITEMS) => return reconnect(device_info, h_radio),
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_CANCELLED) => ErrorKind::Cancelled,
+        res if err_eq(res, ERROR_NOT_AUTHENTICATED) => ErrorKind::Cancelled,
+        res if err_eq(res, ERROR_ACCESS_DENIED) => ErrorKind::AccessDenied,
+        // including ERROR_REVISION_MISMATCH, which would mean dwSize was set wrong
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothAuthenticateDeviceEx", h_res).into())
+}
+
+/// Answers the authentication requests of a device with a [`PairingAgent`], until it's dropped.
+struct AuthRegistration<'a> {
+    handle: isize,
+    // boxed, so the pointer the callback gets stays valid when the registration moves
+    _context: Box<AuthContext<'a>>,
+}
+
+struct AuthContext<'a> {
+    agent: &'a dyn PairingAgent,
+    h_radio: Option<HANDLE>,
+}
+
+impl<'a> AuthRegistration<'a> {
+    fn register(device_info: &BLUETOOTH_DEVICE_INFO, h_radio: Option<HANDLE>, agent: &'a dyn PairingAgent) -> Result<Self, Win32Err> {
+        let context = Box::new(AuthContext { agent, h_radio });
+        let param = &*context as *const AuthContext as *const c_void;
+
+        // SAFETY: the context outlives the registration, since the registration is dropped first
+        let handle = unsafe { with_err::bluetooth_register_for_authentication(device_info, Some(answer_authentication), param)? };
+
+        Ok(Self { handle, _context: context })
     }
 }
 
-impl From<BluetoothGetDeviceInfoErr> for ConnectToDeviceError {
-    fn from(err: BluetoothGetDeviceInfoErr) -> Self {
-        match err {
-            BluetoothGetDeviceInfoErr::InvalidMacAddress => ConnectToDeviceError::InvalidMacAddress,
-            BluetoothGetDeviceInfoErr::BluetoothError => ConnectToDeviceError::BluetoothError,
-            BluetoothGetDeviceInfoErr::Other(err) => ConnectToDeviceError::UnhandledWin32ApiErr(err),
-        }
+impl Drop for AuthRegistration<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the handle was registered above and is only unregistered here
+        let _ = unsafe { BluetoothUnregisterAuthentication(self.handle) };
     }
 }
 
-impl From<BluetoothRemoveDeviceErr> for ConnectToDeviceError {
-    fn from(err: BluetoothRemoveDeviceErr) -> Self {
-        match err {
-            BluetoothRemoveDeviceErr::InvalidMacAddress => ConnectToDeviceError::InvalidMacAddress,
-            BluetoothRemoveDeviceErr::BluetoothError => ConnectToDeviceError::BluetoothError,
-            BluetoothRemoveDeviceErr::Other(err) => ConnectToDeviceError::UnhandledWin32ApiErr(err),
+/// Answers a request from the OS with the agent of the [`AuthContext`] `param` points to
+unsafe extern "system" fn answer_authentication(param: *const c_void, params: *const BLUETOOTH_AUTHENTICATION_CALLBACK_PARAMS) -> BOOL {
+    // SAFETY: param is the registration's context, which outlives the registration, and params are given by the OS
+    let (context, params) = unsafe { (&*(param as *const AuthContext), &*params) };
+    let mac_address = MacAddress::from(params.deviceInfo.Address);
+
+    let mut response = BLUETOOTH_AUTHENTICATE_RESPONSE {
+        bthAddressRemote: params.deviceInfo.Address,
+        authMethod: params.authenticationMethod,
+        .. Default::default()
+    };
+
+    let accepted = match params.authenticationMethod {
+        BLUETOOTH_AUTHENTICATION_METHOD_LEGACY => match context.agent.request_pin(mac_address) {
+            Some(pin) if (1..=16).contains(&pin.len()) => {
+                let mut pin_info = BLUETOOTH_PIN_INFO { pinLength: pin.len() as u8, .. Default::default() };
+                pin_info.pin[..pin.len()].copy_from_slice(pin.as_bytes());
+
+                response.Anonymous.pinInfo = pin_info;
+                true
+            }
+            // including PINs longer than the 16 bytes bluetooth allows
+            _ => false,
+        },
+        BLUETOOTH_AUTHENTICATION_METHOD_PASSKEY => match context.agent.request_passkey(mac_address) {
+            Some(passkey) => {
+                response.Anonymous.passkeyInfo = BLUETOOTH_PASSKEY_INFO { passkey };
+                true
+            }
+            None => false,
+        },
+        BLUETOOTH_AUTHENTICATION_METHOD_NUMERIC_COMPARISON => {
+            // SAFETY: the OS sets the numeric value for numeric comparison
+            let passkey = unsafe { params.Anonymous.Numeric_Value };
+            response.Anonymous.numericCompInfo = BLUETOOTH_NUMERIC_COMPARISON_INFO { NumericValue: passkey };
+
+            context.agent.confirm_passkey(mac_address, passkey)
         }
-    }
-}
\ No newline at end of file
+        BLUETOOTH_AUTHENTICATION_METHOD_PASSKEY_NOTIFICATION => {
+            // SAFETY: the OS sets the passkey for passkey notification
+            let passkey = unsafe { params.Anonymous.Passkey };
+            response.Anonymous.passkeyInfo = BLUETOOTH_PASSKEY_INFO { passkey };
+
+            context.agent.display_passkey(mac_address, passkey);
+            true
+        }
+        // out of band pairing needs data from e.g. NFC, which agents can't give
+        _ => false,
+    };
+
+    response.negativeResponse = (!accepted).into();
+
+    // if the response can't be sent, authentication fails and reports the error instead
+    // SAFETY: the response is fully initialized, and the radio handle is open until the registration is dropped
+    let _ = unsafe { BluetoothSendAuthenticationResponseEx(context.h_radio, &response) };
+
+    true.into()
+}
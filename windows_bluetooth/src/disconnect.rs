@@ -0,0 +1,60 @@
+use std::ffi::c_void;
+use windows::Win32::Foundation::{ERROR_DEVICE_NOT_CONNECTED, ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_NOT_FOUND};
+use windows::Win32::System::IO::DeviceIoControl;
+use crate::{err_eq, open_radio_or_first, with_err, BluetoothError, ErrorKind, Failure, MacAddress, Operation, Radio};
+use crate::connect_os::device_info_of;
+use crate::with_err::Win32Err;
+
+/// `BTH_CTL(BTH_IOCTL_BASE + 0x03)` from bthioctl.h, which isn't exposed by the windows crate
+const IOCTL_BTH_DISCONNECT_DEVICE: u32 = 0x0041_000C;
+
+/// Drops the connection to a device through `radio`, or the first radio if `None`, but keeps it paired, so it can be
+/// reconnected without pairing mode.
+pub fn disconnect_device(mac_address: MacAddress, radio: Option<&Radio>) -> Result<(), BluetoothError> {
+    disconnect(mac_address, radio).map_err(|err| err.during(Operation::Disconnect, Some(mac_address)))
+}
+
+fn disconnect(mac_address: MacAddress, radio: Option<&Radio>) -> Result<(), Failure> {
+    let h_radio = open_radio_or_first(radio)?;
+
+    let mut device_info = device_info_of(mac_address);
+
+    // SAFETY: device info is properly initialized, not being used concurrently
+    unsafe { with_err::bluetooth_get_device_info(Some(h_radio.handle()), &mut device_info)?; }
+
+    if !device_info.fConnected.as_bool() {
+        return Ok(());
+    }
+
+    // SAFETY: all bit patterns are valid for both fields of the union; the upper two bytes are zeroed
+    let bth_addr: u64 = unsafe { device_info.Address.Anonymous.ullLong };
+
+    // SAFETY: radio handle is open, the input buffer is a BTH_ADDR which outlives the call, and there's no output
+    let res = unsafe {
+        DeviceIoControl(
+            h_radio.handle(),
+            IOCTL_BTH_DISCONNECT_DEVICE,
+            Some(&bth_addr as *const u64 as *const c_void),
+            size_of::<u64>() as _,
+            None,
+            0,
+            None,
+            None,
+        )
+    };
+
+    let Err(err) = res else {
+        return Ok(());
+    };
+
+    let kind = match err.code() {
+        // disconnected on its own in the meantime
+        res if err_eq(res, ERROR_DEVICE_NOT_CONNECTED) => return Ok(()),
+        res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "DeviceIoControl(IOCTL_BTH_DISCONNECT_DEVICE)", err.code()).into())
+}
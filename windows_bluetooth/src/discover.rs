@@ -1,84 +1,150 @@
+use std::iter::FusedIterator;
 use std::ptr;
-use windows::Win32::Devices::Bluetooth::{BluetoothFindDeviceClose, BluetoothFindFirstDevice, BluetoothFindNextDevice, BLUETOOTH_DEVICE_INFO, BLUETOOTH_DEVICE_SEARCH_PARAMS};
-use windows::Win32::Foundation::{ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_NO_MORE_ITEMS, ERROR_OUTOFMEMORY, ERROR_REVISION_MISMATCH, FALSE, HANDLE, TRUE, WIN32_ERROR};
+use windows::Win32::Devices::Bluetooth::{BluetoothFindDeviceClose, BluetoothFindFirstDevice, BluetoothFindNextDevice, BLUETOOTH_DEVICE_INFO, BLUETOOTH_DEVICE_SEARCH_PARAMS, HBLUETOOTH_DEVICE_FIND};
+use windows::Win32::Foundation::{ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_NO_MORE_ITEMS, ERROR_OUTOFMEMORY, HANDLE, WIN32_ERROR};
 use windows::core::Error as WinErr;
 use crate::bluetooth_device::BluetoothDevice;
+use crate::with_err::Win32Err;
+use crate::connect_os::device_info_of;
+use crate::{open_radio, with_err, BluetoothError, DiscoverOptions, ErrorKind, Failure, MacAddress, Operation, Radio, RadioHandle};
 
 #[inline]
 fn err_eq(win_err: &WinErr, win32_err: WIN32_ERROR) -> bool {
     crate::err_eq(win_err.code(), win32_err)
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum DiscoverDevicesError {
-    #[error("Not enough storage is available to complete this operation")]
-    OutOfMemory,
-    #[error("Error with bluetooth adapter, bluetooth may be off, or no bluetooth adapter exists.")]
-    BluetoothError,
-    #[error("Unhandled error from Windows api: {0}")]
-    UnhandledWin32ApiErr(WinErr),
+fn search_err(function: &'static str, err: WinErr) -> BluetoothError {
+    let kind = match &err {
+        // since h_find is known to not be null, I'm assuming this can only happen if bluetooth is disconnected between this call and the last call
+        err if err_eq(err, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        err if err_eq(err, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        err if err_eq(err, ERROR_OUTOFMEMORY) => ErrorKind::OutOfMemory,
+        // including ERROR_REVISION_MISMATCH and ERROR_INVALID_PARAMETER, which would mean the params were set wrong
+        _ => ErrorKind::Unhandled,
+    };
+
+    Failure::from(Win32Err { kind, function, err: Some(err) }).during(Operation::Discover, None)
 }
 
-pub fn discover_devices() -> Result<Vec<BluetoothDevice>, DiscoverDevicesError> {
-    let search_params = BLUETOOTH_DEVICE_SEARCH_PARAMS {
-        dwSize: size_of::<BLUETOOTH_DEVICE_SEARCH_PARAMS>() as _,
-        fReturnAuthenticated: TRUE,
-        fReturnRemembered: TRUE,
-        fReturnUnknown: TRUE,
-        fReturnConnected: TRUE,
-        fIssueInquiry: FALSE,
-        cTimeoutMultiplier: 5,
-        hRadio: HANDLE(ptr::null_mut()),
-    };
+/// Lists the devices known to `radio`, or to every radio if `None`, which `options` selects.
+///
+/// With an inquiry, this blocks for the inquiry's whole duration.
+pub fn discover_devices(radio: Option<&Radio>, options: &DiscoverOptions) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+    DeviceSearch::new(radio, options)?.collect()
+}
 
-    let mut device_info = BLUETOOTH_DEVICE_INFO {
-        dwSize: size_of::<BLUETOOTH_DEVICE_INFO>() as _,
-        .. unsafe {std::mem::zeroed() } // SAFETY: all of BLUETOOTH_DEVICE_INFO's fields can and should be zeroed 
-    };
-    
-    let mut found_devices = Vec::new();
-
-    // SAFETY: not used concurrently & dwSize has been set correctly
-    let h_find = match unsafe { BluetoothFindFirstDevice(&search_params, &mut device_info) } {
-        Ok(h_find) => h_find,
-        Err(err) if err_eq(&err, ERROR_REVISION_MISMATCH) => unreachable!("dwSize should be correct"),
-        Err(err) if err_eq(&err, ERROR_INVALID_PARAMETER) => unreachable!("params properly initialized"),
-        Err(err) if err_eq(&err, ERROR_OUTOFMEMORY) => return Err(DiscoverDevicesError::OutOfMemory),
-        Err(err) if err_eq(&err, ERROR_INVALID_HANDLE) => return Err(DiscoverDevicesError::BluetoothError),
-        Err(err) if err_eq(&err, ERROR_GEN_FAILURE) => return Err(DiscoverDevicesError::BluetoothError),
-        Err(err) if err_eq(&err, ERROR_NO_MORE_ITEMS) => return Ok(found_devices),
-        Err(err) => return Err(DiscoverDevicesError::UnhandledWin32ApiErr(err)),
+/// Looks up a single device known to `radio`, or to any radio if `None`, without searching for the others.
+pub fn get_device(mac_address: MacAddress, radio: Option<&Radio>) -> Result<BluetoothDevice, BluetoothError> {
+    let get = || -> Result<BluetoothDevice, Failure> {
+        let h_radio = open_radio(radio)?;
+        let mut device_info = device_info_of(mac_address);
+
+        // SAFETY: device info is properly initialized, not being used concurrently
+        unsafe { with_err::bluetooth_get_device_info(h_radio.as_ref().map(RadioHandle::handle), &mut device_info)?; }
+
+        // SAFETY: device info's state was given by OS
+        Ok(unsafe { BluetoothDevice::from_win32_bluetooth_device(&device_info) })
     };
 
-    loop {
-        // SAFETY: device info should be correctly formed
-        found_devices.push(unsafe { BluetoothDevice::from_win32_bluetooth_device(&device_info) });
-
-        // SAFETY: handle is valid & device info's state was given by OS, so assumed to be safe; not being concurrently modified
-        match unsafe { BluetoothFindNextDevice(h_find, &mut device_info) } {
-            Ok(_) => {} // data valid
-            Err(err) if err_eq(&err, ERROR_NO_MORE_ITEMS) => break,
-            Err(err) => {
-                // even if closing the handle errors, only return the first error
-                let _ = unsafe { BluetoothFindDeviceClose(h_find) };
-
-                return match err {
-                    // since h_find is known to not be null, I'm assuming this can only happen if bluetooth is disconnected between this call and the last call
-                    err if err_eq(&err, ERROR_INVALID_HANDLE) => Err(DiscoverDevicesError::BluetoothError),
-                    err if err_eq(&err, ERROR_GEN_FAILURE) => Err(DiscoverDevicesError::BluetoothError),
-                    err if err_eq(&err, ERROR_OUTOFMEMORY) => Err(DiscoverDevicesError::OutOfMemory),
-                    err => Err(DiscoverDevicesError::UnhandledWin32ApiErr(err)),
+    get().map_err(|err| err.during(Operation::Get, Some(mac_address)))
+}
+
+/// A search for devices, which yields each device as it's read and closes the search when dropped.
+///
+/// Windows runs the whole inquiry before reporting the first device, so the first call to [`new`](Self::new)
+/// blocks for the inquiry's duration, but reading each device, e.g. its services, only happens as it's yielded.
+/// After an error, the search is closed and yields nothing more.
+pub struct DeviceSearch {
+    h_find: Option<HBLUETOOTH_DEVICE_FIND>,
+    device_info: BLUETOOTH_DEVICE_INFO,
+    /// Whether `device_info` holds a device which hasn't been yielded yet
+    pending: bool,
+    // kept open for as long as the search, and closed after it, since fields are dropped after `drop`
+    _h_radio: Option<RadioHandle>,
+}
+
+impl DeviceSearch {
+    /// Starts searching the devices known to `radio`, or to every radio if `None`, which `options` selects.
+    pub fn new(radio: Option<&Radio>, options: &DiscoverOptions) -> Result<Self, BluetoothError> {
+        let h_radio = open_radio(radio).map_err(|err| err.during(Operation::Discover, None))?;
+
+        let search_params = BLUETOOTH_DEVICE_SEARCH_PARAMS {
+            dwSize: size_of::<BLUETOOTH_DEVICE_SEARCH_PARAMS>() as _,
+            fReturnAuthenticated: options.returns_authenticated().into(),
+            fReturnRemembered: options.returns_remembered().into(),
+            fReturnUnknown: options.returns_unknown().into(),
+            fReturnConnected: options.returns_connected().into(),
+            fIssueInquiry: options.inquiry_duration().is_some().into(),
+            cTimeoutMultiplier: options.inquiry_units(),
+            hRadio: h_radio.as_ref().map_or(HANDLE(ptr::null_mut()), |h_radio| h_radio.handle()),
+        };
+
+        let mut search = Self {
+            h_find: None,
+            device_info: BLUETOOTH_DEVICE_INFO {
+                dwSize: size_of::<BLUETOOTH_DEVICE_INFO>() as _,
+                .. unsafe { std::mem::zeroed() } // SAFETY: all of BLUETOOTH_DEVICE_INFO's fields can and should be zeroed
+            },
+            pending: false,
+            _h_radio: h_radio,
+        };
+
+        // SAFETY: not used concurrently & dwSize has been set correctly
+        match unsafe { BluetoothFindFirstDevice(&search_params, &mut search.device_info) } {
+            Ok(h_find) => {
+                search.h_find = Some(h_find);
+                search.pending = true;
+            }
+            Err(err) if err_eq(&err, ERROR_NO_MORE_ITEMS) => {} // nothing found, so there's no search to close
+            Err(err) => return Err(search_err("BluetoothFindFirstDevice", err)),
+        }
+
+        Ok(search)
+    }
+
+    /// Ends the search, after which nothing more is yielded
+    fn close(&mut self) -> Result<(), BluetoothError> {
+        let Some(h_find) = self.h_find.take() else {
+            return Ok(());
+        };
+
+        // SAFETY: handle is open and valid, and is only closed once since it was taken out of the search
+        unsafe { BluetoothFindDeviceClose(h_find) }.map_err(|err| search_err("BluetoothFindDeviceClose", err))
+    }
+}
+
+impl Iterator for DeviceSearch {
+    type Item = Result<BluetoothDevice, BluetoothError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.pending {
+            let h_find = self.h_find?;
+
+            // SAFETY: handle is valid & device info's state was given by OS, so assumed to be safe; not being concurrently modified
+            match unsafe { BluetoothFindNextDevice(h_find, &mut self.device_info) } {
+                Ok(_) => {} // data valid
+                Err(err) if err_eq(&err, ERROR_NO_MORE_ITEMS) => return self.close().err().map(Err),
+                Err(err) => {
+                    // even if closing the handle errors, only return the first error
+                    let _ = self.close();
+
+                    return Some(Err(search_err("BluetoothFindNextDevice", err)));
                 }
             }
         }
+
+        self.pending = false;
+
+        // SAFETY: device info should be correctly formed
+        Some(Ok(unsafe { BluetoothDevice::from_win32_bluetooth_device(&self.device_info) }))
     }
+}
 
-    // SAFETY: handle is open and valid, search is complete, not called concurrently
-    match unsafe { BluetoothFindDeviceClose(h_find) } {
-        Ok(_) => Ok(found_devices),
-        // since h_find is known to not be null, I'm assuming this can only happen if bluetooth is disconnected between this call and the last call
-        Err(err) if err_eq(&err, ERROR_INVALID_HANDLE) => Err(DiscoverDevicesError::BluetoothError),
-        Err(err) if err_eq(&err, ERROR_GEN_FAILURE) => Err(DiscoverDevicesError::BluetoothError),
-        Err(err) => Err(DiscoverDevicesError::UnhandledWin32ApiErr(err))
+impl FusedIterator for DeviceSearch {}
+
+impl Drop for DeviceSearch {
+    fn drop(&mut self) {
+        // the search may be dropped part way through, and there's no one left to report an error to
+        let _ = self.close();
     }
-}
\ No newline at end of file
+}
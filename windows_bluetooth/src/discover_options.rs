@@ -0,0 +1,165 @@
+use std::time::Duration;
+use crate::BluetoothDevice;
+
+/// Which devices a scan returns, and whether it first searches for devices in range.
+///
+/// By default, a scan only lists the devices the OS already knows about, which is quick but misses devices that
+/// haven't been seen yet, e.g. headphones in pairing mode.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiscoverOptions {
+    inquiry: Option<Duration>,
+    authenticated: bool,
+    remembered: bool,
+    unknown: bool,
+    connected: bool,
+}
+
+impl DiscoverOptions {
+    /// The granularity of an inquiry's duration, as set by the bluetooth spec
+    pub const INQUIRY_UNIT: Duration = Duration::from_millis(1280);
+    /// The longest inquiry Windows allows, 48 units
+    pub const MAX_INQUIRY: Duration = Duration::from_millis(1280 * 48);
+
+    /// Lists every known device, without an inquiry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Searches for devices in range for about `duration` before listing them, which finds devices that aren't known yet.
+    ///
+    /// The duration is rounded up to a multiple of [`INQUIRY_UNIT`](Self::INQUIRY_UNIT),
+    /// and clamped to between one unit and [`MAX_INQUIRY`](Self::MAX_INQUIRY).
+    pub fn inquiry(mut self, duration: Duration) -> Self {
+        self.inquiry = Some(duration.clamp(Self::INQUIRY_UNIT, Self::MAX_INQUIRY));
+        self
+    }
+
+    /// Whether to return paired devices
+    pub fn authenticated(mut self, authenticated: bool) -> Self {
+        self.authenticated = authenticated;
+        self
+    }
+
+    /// Whether to return devices the OS remembers, paired or not
+    pub fn remembered(mut self, remembered: bool) -> Self {
+        self.remembered = remembered;
+        self
+    }
+
+    /// Whether to return devices the OS doesn't remember, such as ones found by an inquiry
+    pub fn unknown(mut self, unknown: bool) -> Self {
+        self.unknown = unknown;
+        self
+    }
+
+    /// Whether to return connected devices
+    pub fn connected(mut self, connected: bool) -> Self {
+        self.connected = connected;
+        self
+    }
+
+    /// How long to search for devices in range, if at all
+    pub fn inquiry_duration(&self) -> Option<Duration> {
+        self.inquiry
+    }
+
+    pub fn returns_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    pub fn returns_remembered(&self) -> bool {
+        self.remembered
+    }
+
+    pub fn returns_unknown(&self) -> bool {
+        self.unknown
+    }
+
+    pub fn returns_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Whether `device` falls into any of the categories to return, for backends that can't filter by themselves
+    pub fn matches(&self, device: &BluetoothDevice) -> bool {
+        (self.authenticated && device.authenticated)
+            || (self.remembered && device.remembered)
+            || (self.unknown && !device.remembered)
+            || (self.connected && device.connected)
+    }
+
+    /// The inquiry's duration in [`INQUIRY_UNIT`](Self::INQUIRY_UNIT)s, or 0 without an inquiry
+    #[cfg(windows)]
+    pub(crate) fn inquiry_units(&self) -> u8 {
+        self.inquiry.map_or(0, |duration| {
+            duration.as_millis().div_ceil(Self::INQUIRY_UNIT.as_millis()) as u8
+        })
+    }
+}
+
+impl Default for DiscoverOptions {
+    fn default() -> Self {
+        Self {
+            inquiry: None,
+            authenticated: true,
+            remembered: true,
+            unknown: true,
+            connected: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::BluetoothDevice;
+    use crate::backend::test_devices::{headphones, speaker};
+    use super::DiscoverOptions;
+
+    fn none() -> DiscoverOptions {
+        DiscoverOptions::new().authenticated(false).remembered(false).unknown(false).connected(false)
+    }
+
+    /// Which of a paired, a remembered, an unknown and a connected unknown device `options` matches
+    fn matching(options: DiscoverOptions) -> [bool; 4] {
+        let devices: [BluetoothDevice; 4] = [
+            headphones().build(),
+            headphones().authenticated(false).build(),
+            speaker().build(),
+            speaker().connected(true).build(),
+        ];
+
+        devices.map(|device| options.matches(&device))
+    }
+
+    #[test]
+    fn matches_each_category_on_its_own() {
+        assert_eq!(matching(none()), [false, false, false, false]);
+        assert_eq!(matching(none().authenticated(true)), [true, false, false, false]);
+        assert_eq!(matching(none().remembered(true)), [true, true, false, false]);
+        assert_eq!(matching(none().unknown(true)), [false, false, true, true]);
+        assert_eq!(matching(none().connected(true)), [false, false, false, true]);
+        assert_eq!(matching(DiscoverOptions::new()), [true, true, true, true]);
+    }
+
+    #[test]
+    fn inquiry_is_clamped() {
+        assert_eq!(DiscoverOptions::new().inquiry_duration(), None);
+        assert_eq!(DiscoverOptions::new().inquiry(Duration::ZERO).inquiry_duration(), Some(DiscoverOptions::INQUIRY_UNIT));
+        assert_eq!(DiscoverOptions::new().inquiry(Duration::from_secs(5)).inquiry_duration(), Some(Duration::from_secs(5)));
+        assert_eq!(DiscoverOptions::new().inquiry(Duration::from_secs(600)).inquiry_duration(), Some(DiscoverOptions::MAX_INQUIRY));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn inquiry_rounds_up_to_whole_units() {
+        let units = |millis| DiscoverOptions::new().inquiry(Duration::from_millis(millis)).inquiry_units();
+
+        assert_eq!(DiscoverOptions::new().inquiry_units(), 0);
+        assert_eq!(units(1), 1);
+        assert_eq!(units(1280), 1);
+        assert_eq!(units(1281), 2);
+        assert_eq!(units(1280 * 48), 48);
+        assert_eq!(units(u64::MAX), 48);
+    }
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fmt;
+use crate::{MacAddress, Operation};
+
+type Source = Box<dyn Error + Send + Sync + 'static>;
+
+/// What went wrong, independent of the platform and operation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    OutOfMemory,
+    /// Bluetooth is off, or there's no bluetooth adapter
+    BluetoothUnavailable,
+    /// No device has the MAC address
+    InvalidMacAddress,
+    /// Authentication failed, or the user cancelled it
+    Cancelled,
+    AccessDenied,
+    /// The device exists, but isn't remembered
+    NotRemembered,
+    /// The device is paired, but has no installed services to connect, which re-pairing it usually fixes
+    NoServices,
+    /// An error the platform returned which isn't one of the other kinds; its [`source`](Error::source) has the details
+    Unhandled,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::OutOfMemory => "Not enough storage is available to complete this operation",
+            ErrorKind::BluetoothUnavailable => "Error with bluetooth adapter, bluetooth may be off, or no bluetooth adapter exists.",
+            ErrorKind::InvalidMacAddress => "The provided MAC Address was invalid",
+            ErrorKind::Cancelled => "The user wasn't authenticated or canceled the operation",
+            ErrorKind::AccessDenied => "Insufficient permissions or bluetooth device cannot be paired to",
+            ErrorKind::NotRemembered => "The device exists, but wasn't remembered in the first place",
+            ErrorKind::NoServices => "The device is paired, but has no services to connect to, try re-pairing it",
+            ErrorKind::Unhandled => "Unhandled error from the bluetooth stack",
+        })
+    }
+}
+
+/// An error from any bluetooth operation, along with which operation failed and on which device.
+///
+/// Platform errors are kept as the [`source`](Error::source), and Win32 error codes as the [`code`](Self::code).
+#[derive(Debug)]
+pub struct BluetoothError {
+    kind: ErrorKind,
+    operation: Operation,
+    mac_address: Option<MacAddress>,
+    code: Option<u32>,
+    source: Option<Source>,
+}
+
+impl BluetoothError {
+    pub fn new(kind: ErrorKind, operation: Operation) -> Self {
+        Self {
+            kind,
+            operation,
+            mac_address: None,
+            code: None,
+            source: None,
+        }
+    }
+
+    /// The device the operation was on
+    pub fn with_mac_address(mut self, mac_address: MacAddress) -> Self {
+        self.mac_address = Some(mac_address);
+        self
+    }
+
+    pub fn with_code(mut self, code: u32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<Source>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The device the operation was on, if it was on one
+    pub fn mac_address(&self) -> Option<MacAddress> {
+        self.mac_address
+    }
+
+    /// The raw Win32 error code, like 1168 for `ERROR_NOT_FOUND`, or the whole `HRESULT` for ones which aren't Win32 errors
+    pub fn code(&self) -> Option<u32> {
+        self.code
+    }
+}
+
+impl fmt::Display for BluetoothError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let device = match self.mac_address {
+            Some(mac_address) => mac_address.to_string(),
+            None => "device".to_owned(),
+        };
+
+        match self.operation {
+            Operation::Discover => write!(f, "Couldn't discover devices")?,
+            Operation::Get => write!(f, "Couldn't look up {device}")?,
+            Operation::Connect => write!(f, "Couldn't connect to {device}")?,
+            Operation::Disconnect => write!(f, "Couldn't disconnect {device}")?,
+            Operation::Remove => write!(f, "Couldn't remove {device}")?,
+        }
+
+        write!(f, ": {}", self.kind)?;
+
+        // the details of an unhandled error are only in its source, which most callers don't print
+        match (self.kind, &self.source) {
+            (ErrorKind::Unhandled, Some(source)) => write!(f, ": {source}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Error for BluetoothError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|source| source as _)
+    }
+}
+
+/// An error from platform code which hasn't been attributed to an operation yet, see [`Failure::during`].
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) kind: ErrorKind,
+    pub(crate) code: Option<u32>,
+    pub(crate) source: Option<Source>,
+}
+
+impl Failure {
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            code: None,
+            source: None,
+        }
+    }
+
+    pub(crate) fn during(self, operation: Operation, mac_address: Option<MacAddress>) -> BluetoothError {
+        BluetoothError {
+            kind: self.kind,
+            operation,
+            mac_address,
+            code: self.code,
+            source: self.source,
+        }
+    }
+}
+
+impl From<ErrorKind> for Failure {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+/// The Win32 error code an `HRESULT` wraps, or the whole `HRESULT` if it doesn't wrap one
+#[cfg(windows)]
+pub(crate) fn code_of_hresult(h_res: i32) -> u32 {
+    const FACILITY_WIN32: u32 = 7;
+
+    match ((h_res as u32) >> 16) & 0x1FFF {
+        FACILITY_WIN32 => h_res as u32 & 0xFFFF,
+        _ => h_res as u32,
+    }
+}
@@ -1,17 +1,45 @@
+mod activity;
+mod backend;
+#[cfg(windows)]
 mod discover;
+mod discover_options;
 mod bluetooth_device;
+#[cfg(windows)]
 mod connect_os;
+#[cfg(windows)]
+mod disconnect;
+mod error;
+mod pairing;
+mod radio;
+#[cfg(windows)]
 mod remove;
+#[cfg(windows)]
 pub(crate) mod with_err;
+mod watcher;
 
+#[cfg(windows)]
 use windows::core::HRESULT;
+#[cfg(windows)]
 use windows::Win32::Foundation::WIN32_ERROR;
+pub use activity::*;
+pub use backend::*;
+#[cfg(windows)]
 pub use discover::*;
+pub use discover_options::DiscoverOptions;
 pub use bluetooth_device::*;
+#[cfg(windows)]
 pub use connect_os::*;
+#[cfg(windows)]
+pub use disconnect::*;
+pub use error::*;
+pub use pairing::{format_passkey, PairingAgent};
+pub use radio::*;
+#[cfg(windows)]
 pub use remove::*;
+pub use watcher::*;
 
+#[cfg(windows)]
 #[inline]
 fn err_eq(h_res: HRESULT, win32_err: WIN32_ERROR) -> bool {
     h_res == HRESULT::from_win32(win32_err.0)
-}
\ No newline at end of file
+}
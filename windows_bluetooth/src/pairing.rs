@@ -0,0 +1,48 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use crate::MacAddress;
+
+/// Answers a device's requests while pairing with it, in place of the OS's own pairing prompts.
+///
+/// Backends call the agent from the thread pairing with the device, and block until it answers,
+/// so an agent which asks the user should hand the request over to the UI thread and wait for the reply.
+pub trait PairingAgent: Send + Sync {
+    /// Asks for the PIN of a device using legacy pairing, e.g. `0000` for most headsets, or `None` to reject it.
+    fn request_pin(&self, mac_address: MacAddress) -> Option<String>;
+
+    /// Asks for the passkey the device displays, or `None` to reject it.
+    fn request_passkey(&self, mac_address: MacAddress) -> Option<u32>;
+
+    /// Asks whether the device displays the same 6 digit `passkey`, accepting the pairing if it does.
+    fn confirm_passkey(&self, mac_address: MacAddress, passkey: u32) -> bool;
+
+    /// Shows the `passkey` to type on the device, which pairs once it's typed, so this shouldn't wait for the user.
+    fn display_passkey(&self, mac_address: MacAddress, passkey: u32);
+}
+
+/// Formats a passkey the way devices display it, as 6 digits with leading zeros
+pub fn format_passkey(passkey: u32) -> String {
+    format!("{passkey:06}")
+}
+
+/// The agent a backend pairs with, which can be set after the backend is shared.
+#[derive(Clone, Default)]
+pub(crate) struct AgentSlot(Arc<Mutex<Option<Arc<dyn PairingAgent>>>>);
+
+impl AgentSlot {
+    pub(crate) fn set(&self, agent: Arc<dyn PairingAgent>) {
+        *self.0.lock().expect("pairing agent shouldn't be poisoned") = Some(agent);
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<dyn PairingAgent>> {
+        self.0.lock().expect("pairing agent shouldn't be poisoned").clone()
+    }
+}
+
+impl fmt::Debug for AgentSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AgentSlot")
+            .field(&self.get().map(|_| "PairingAgent"))
+            .finish()
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{DeviceClass, MacAddress};
+
+#[cfg(windows)]
+mod win32;
+
+#[cfg(windows)]
+pub use win32::discover_radios;
+#[cfg(windows)]
+pub(crate) use win32::{open_radio, open_radio_or_first, RadioHandle};
+
+/// A local bluetooth adapter, such as a built-in one or a USB dongle.
+///
+/// Operations given a radio look it up again by its address, so a radio that was unplugged in the meantime fails
+/// with a `BluetoothError` instead of falling back to another one.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Radio {
+    pub name: String,
+    pub address: MacAddress,
+    /// Bluetooth SIG company identifier of the radio's manufacturer
+    pub manufacturer: u16,
+    /// Manufacturer specific revision of the radio's link manager
+    pub lmp_subversion: u16,
+    pub class: DeviceClass,
+}
+
+impl Radio {
+    /// The manufacturer's name, if it's one of the common radio manufacturers
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        match self.manufacturer {
+            2 => Some("Intel"),
+            6 => Some("Microsoft"),
+            10 => Some("Qualcomm (CSR)"),
+            13 => Some("Texas Instruments"),
+            15 => Some("Broadcom"),
+            29 => Some("Qualcomm"),
+            70 => Some("MediaTek"),
+            72 => Some("Marvell"),
+            76 => Some("Apple"),
+            93 => Some("Realtek"),
+            _ => None,
+        }
+    }
+}
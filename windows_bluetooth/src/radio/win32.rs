@@ -0,0 +1,95 @@
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use windows::Win32::Devices::Bluetooth::BLUETOOTH_RADIO_INFO;
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use crate::with_err::{self, Win32Err};
+use crate::{BluetoothError, DeviceClass, ErrorKind, Failure, MacAddress, Operation};
+use super::Radio;
+
+/// An open radio handle, closed when dropped
+#[derive(Debug)]
+pub(crate) struct RadioHandle(HANDLE);
+
+impl RadioHandle {
+    pub(crate) fn handle(&self) -> HANDLE {
+        self.0
+    }
+
+    fn info(&self) -> Result<BLUETOOTH_RADIO_INFO, Win32Err> {
+        let mut radio_info = BLUETOOTH_RADIO_INFO {
+            dwSize: size_of::<BLUETOOTH_RADIO_INFO>() as _,
+            .. unsafe { std::mem::zeroed() } // SAFETY: all of BLUETOOTH_RADIO_INFO's fields can and should be zeroed
+        };
+
+        // SAFETY: handle is open, dwSize has been set correctly
+        unsafe { with_err::bluetooth_get_radio_info(self.0, &mut radio_info)?; }
+
+        Ok(radio_info)
+    }
+}
+
+impl Drop for RadioHandle {
+    fn drop(&mut self) {
+        // SAFETY: the handle is open and only closed here
+        let _ = unsafe { CloseHandle(self.0) };
+    }
+}
+
+impl Radio {
+    fn from_win32_radio_info(radio_info: &BLUETOOTH_RADIO_INFO) -> Self {
+        let name = OsString::from_wide(&radio_info.szName)
+            .to_string_lossy()
+            .trim_end_matches('\0')
+            .to_owned();
+
+        Self {
+            name,
+            address: MacAddress::from(radio_info.address),
+            manufacturer: radio_info.manufacturer,
+            lmp_subversion: radio_info.lmpSubversion,
+            class: DeviceClass::from_raw(radio_info.ulClassofDevice),
+        }
+    }
+}
+
+fn find_radios() -> Result<Vec<RadioHandle>, Win32Err> {
+    // SAFETY: every handle is closed when its RadioHandle is dropped
+    let handles = unsafe { with_err::bluetooth_find_radios()? };
+
+    Ok(handles.into_iter().map(RadioHandle).collect())
+}
+
+pub fn discover_radios() -> Result<Vec<Radio>, BluetoothError> {
+    let radios = || -> Result<Vec<Radio>, Win32Err> {
+        find_radios()?
+            .iter()
+            .map(|radio| Ok(Radio::from_win32_radio_info(&radio.info()?)))
+            .collect()
+    };
+
+    radios().map_err(|err| Failure::from(err).during(Operation::Discover, None))
+}
+
+/// Opens the given radio, or `None` to leave picking one to Windows
+pub(crate) fn open_radio(radio: Option<&Radio>) -> Result<Option<RadioHandle>, Failure> {
+    let Some(radio) = radio else {
+        return Ok(None);
+    };
+
+    for handle in find_radios()? {
+        if MacAddress::from(handle.info()?.address) == radio.address {
+            return Ok(Some(handle));
+        }
+    }
+
+    // e.g. a USB dongle that was unplugged
+    Err(Failure::new(ErrorKind::BluetoothUnavailable))
+}
+
+/// Opens the given radio, or the first one for calls which need a radio handle
+pub(crate) fn open_radio_or_first(radio: Option<&Radio>) -> Result<RadioHandle, Failure> {
+    match open_radio(radio)? {
+        Some(handle) => Ok(handle),
+        None => find_radios()?.into_iter().next().ok_or(Failure::new(ErrorKind::BluetoothUnavailable)),
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use crate::{BluetoothBackend, BluetoothDevice, BluetoothError, DeviceChange, MacAddress};
+
+/// A change in a device between two scans.
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// The device wasn't in the previous scan
+    Appeared { new: BluetoothDevice },
+    Connected { old: BluetoothDevice, new: BluetoothDevice },
+    Disconnected { old: BluetoothDevice, new: BluetoothDevice },
+    /// The device was remembered, but isn't anymore
+    Forgotten { old: BluetoothDevice, new: BluetoothDevice },
+    Renamed { old: BluetoothDevice, new: BluetoothDevice },
+    /// The device isn't in the latest scan
+    Vanished { old: BluetoothDevice },
+}
+
+impl DeviceEvent {
+    pub fn mac_address(&self) -> MacAddress {
+        match self {
+            DeviceEvent::Appeared { new } => new.mac_address,
+            DeviceEvent::Vanished { old } => old.mac_address,
+            DeviceEvent::Connected { new, .. }
+            | DeviceEvent::Disconnected { new, .. }
+            | DeviceEvent::Forgotten { new, .. }
+            | DeviceEvent::Renamed { new, .. } => new.mac_address,
+        }
+    }
+
+    /// The device before the change, if it existed
+    pub fn previous(&self) -> Option<&BluetoothDevice> {
+        match self {
+            DeviceEvent::Appeared { .. } => None,
+            DeviceEvent::Vanished { old }
+            | DeviceEvent::Connected { old, .. }
+            | DeviceEvent::Disconnected { old, .. }
+            | DeviceEvent::Forgotten { old, .. }
+            | DeviceEvent::Renamed { old, .. } => Some(old),
+        }
+    }
+
+    /// The device after the change, if it still exists
+    pub fn current(&self) -> Option<&BluetoothDevice> {
+        match self {
+            DeviceEvent::Vanished { .. } => None,
+            DeviceEvent::Appeared { new }
+            | DeviceEvent::Connected { new, .. }
+            | DeviceEvent::Disconnected { new, .. }
+            | DeviceEvent::Forgotten { new, .. }
+            | DeviceEvent::Renamed { new, .. } => Some(new),
+        }
+    }
+}
+
+/// Keeps the latest scan of devices, and turns each new scan into the events that changed it.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceWatcher {
+    devices: BTreeMap<MacAddress, BluetoothDevice>,
+}
+
+impl DeviceWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The devices from the latest scan, ordered by MAC address
+    pub fn devices(&self) -> impl Iterator<Item = &BluetoothDevice> {
+        self.devices.values()
+    }
+
+    pub fn device(&self, mac_address: MacAddress) -> Option<&BluetoothDevice> {
+        self.devices.get(&mac_address)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Scans with `backend`, and applies the result.
+    pub fn poll<B: BluetoothBackend + ?Sized>(&mut self, backend: &B) -> Result<Vec<DeviceEvent>, BluetoothError> {
+        Ok(self.update(backend.discover_devices()?))
+    }
+
+    /// Replaces the known devices with a complete scan, returning what changed.
+    pub fn update(&mut self, scan: impl IntoIterator<Item = BluetoothDevice>) -> Vec<DeviceEvent> {
+        let mut previous = std::mem::take(&mut self.devices);
+        let mut events = Vec::new();
+
+        for new in scan {
+            match previous.remove(&new.mac_address) {
+                Some(old) => events.extend(changes(&old, &new)),
+                None => events.push(DeviceEvent::Appeared { new: new.clone() }),
+            }
+
+            self.devices.insert(new.mac_address, new);
+        }
+
+        events.extend(previous.into_values().map(|old| DeviceEvent::Vanished { old }));
+
+        events
+    }
+
+    /// Applies a change to a single device, e.g. after refreshing it on its own, returning what changed.
+    pub fn update_device(&mut self, new: BluetoothDevice) -> Vec<DeviceEvent> {
+        match self.devices.insert(new.mac_address, new.clone()) {
+            Some(old) => changes(&old, &new),
+            None => vec![DeviceEvent::Appeared { new }],
+        }
+    }
+
+    /// Drops a single device, e.g. after refreshing it found it's gone, returning what changed.
+    pub fn remove_device(&mut self, mac_address: MacAddress) -> Vec<DeviceEvent> {
+        self.devices.remove(&mac_address)
+            .map(|old| DeviceEvent::Vanished { old })
+            .into_iter()
+            .collect()
+    }
+}
+
+fn changes(old: &BluetoothDevice, new: &BluetoothDevice) -> Vec<DeviceEvent> {
+    let pair = || (old.clone(), new.clone());
+
+    old.diff(new)
+        .into_iter()
+        .filter_map(|change| match change {
+            DeviceChange::Name { .. } => {
+                let (old, new) = pair();
+                Some(DeviceEvent::Renamed { old, new })
+            }
+            DeviceChange::Connected(true) => {
+                let (old, new) = pair();
+                Some(DeviceEvent::Connected { old, new })
+            }
+            DeviceChange::Connected(false) => {
+                let (old, new) = pair();
+                Some(DeviceEvent::Disconnected { old, new })
+            }
+            DeviceChange::Remembered(false) => {
+                let (old, new) = pair();
+                Some(DeviceEvent::Forgotten { old, new })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BluetoothDevice;
+    use crate::backend::test_devices::{headphones, speaker, HEADPHONES, SPEAKER};
+    use super::{DeviceEvent, DeviceWatcher};
+
+    fn watching(devices: impl IntoIterator<Item = BluetoothDevice>) -> DeviceWatcher {
+        let mut watcher = DeviceWatcher::new();
+        watcher.update(devices);
+        watcher
+    }
+
+    #[test]
+    fn devices_appear_and_vanish_between_scans() {
+        let mut watcher = DeviceWatcher::new();
+
+        let events = watcher.update([headphones().build()]);
+        assert!(matches!(&events[..], [DeviceEvent::Appeared { new }] if *new == headphones().build()));
+
+        let events = watcher.update([speaker().build()]);
+        assert!(matches!(
+            &events[..],
+            [DeviceEvent::Appeared { new }, DeviceEvent::Vanished { old }] if *new == speaker().build() && *old == headphones().build()
+        ));
+
+        assert_eq!(watcher.devices().map(BluetoothDevice::mac_address).collect::<Vec<_>>(), [SPEAKER]);
+        assert!(watcher.update([speaker().build()]).is_empty());
+    }
+
+    #[test]
+    fn connecting_and_disconnecting_carry_both_snapshots() {
+        let disconnected = headphones().build();
+        let connected = headphones().connected(true).build();
+        let mut watcher = watching([disconnected.clone()]);
+
+        let events = watcher.update([connected.clone()]);
+        assert!(matches!(&events[..], [DeviceEvent::Connected { old, new }] if *old == disconnected && *new == connected));
+        assert_eq!(watcher.device(HEADPHONES), Some(&connected));
+
+        let events = watcher.update([disconnected.clone()]);
+        assert!(matches!(&events[..], [DeviceEvent::Disconnected { old, new }] if *old == connected && *new == disconnected));
+        assert_eq!(events[0].previous(), Some(&connected));
+        assert_eq!(events[0].current(), Some(&disconnected));
+    }
+
+    #[test]
+    fn renaming_and_forgetting() {
+        let mut watcher = watching([headphones().build()]);
+
+        let renamed = headphones().name("Work Headphones").build();
+        let events = watcher.update([renamed.clone()]);
+        assert!(matches!(&events[..], [DeviceEvent::Renamed { old, new }] if *old == headphones().build() && *new == renamed));
+
+        let forgotten = headphones().name("Work Headphones").remembered(false).authenticated(false).build();
+        let events = watcher.update([forgotten.clone()]);
+        assert!(matches!(&events[..], [DeviceEvent::Forgotten { old, new }] if *old == renamed && *new == forgotten));
+
+        // becoming remembered again isn't an event of its own
+        assert!(watcher.update([renamed]).is_empty());
+    }
+
+    #[test]
+    fn several_changes_in_one_scan_are_separate_events() {
+        let mut watcher = watching([headphones().build()]);
+
+        let events = watcher.update([headphones().name("Work Headphones").connected(true).build()]);
+
+        assert!(matches!(&events[..], [DeviceEvent::Renamed { .. }, DeviceEvent::Connected { .. }]), "{events:?}");
+        assert!(events.iter().all(|event| event.mac_address() == HEADPHONES));
+    }
+
+    #[test]
+    fn single_devices_update_on_their_own() {
+        let mut watcher = watching([headphones().build()]);
+
+        let events = watcher.update_device(speaker().build());
+        assert!(matches!(&events[..], [DeviceEvent::Appeared { new }] if *new == speaker().build()));
+
+        let events = watcher.update_device(headphones().connected(true).build());
+        assert!(matches!(&events[..], [DeviceEvent::Connected { .. }]));
+
+        let events = watcher.remove_device(SPEAKER);
+        assert!(matches!(&events[..], [DeviceEvent::Vanished { old }] if *old == speaker().build()));
+        assert!(watcher.remove_device(SPEAKER).is_empty());
+
+        assert_eq!(watcher.devices().map(BluetoothDevice::mac_address).collect::<Vec<_>>(), [HEADPHONES]);
+    }
+}
@@ -1,42 +1,64 @@
-use windows::core::HRESULT;
-use windows::Win32::Devices::Bluetooth::{BluetoothGetDeviceInfo, BluetoothRemoveDevice, BLUETOOTH_ADDRESS, BLUETOOTH_DEVICE_INFO};
-use windows::Win32::Foundation::{ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_NOT_FOUND, ERROR_REVISION_MISMATCH, ERROR_SUCCESS, HANDLE};
-use crate::{err_eq, MacAddress};
+use std::ffi::c_void;
+use windows::core::{GUID, HRESULT};
+use windows::Win32::Devices::Bluetooth::{BluetoothEnumerateInstalledServices, BluetoothFindFirstRadio, BluetoothFindNextRadio, BluetoothFindRadioClose, BluetoothGetDeviceInfo, BluetoothGetRadioInfo, BluetoothRegisterForAuthenticationEx, BluetoothRemoveDevice, BluetoothSetServiceState, BLUETOOTH_ADDRESS, BLUETOOTH_DEVICE_INFO, BLUETOOTH_FIND_RADIO_PARAMS, BLUETOOTH_RADIO_INFO, PFN_AUTHENTICATION_CALLBACK_EX};
+use windows::Win32::Foundation::{CloseHandle, ERROR_GEN_FAILURE, ERROR_INVALID_HANDLE, ERROR_INVALID_PARAMETER, ERROR_MORE_DATA, ERROR_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_OUTOFMEMORY, ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SUCCESS, HANDLE, WIN32_ERROR};
+use crate::{err_eq, ErrorKind, Failure, MacAddress};
+use crate::error::code_of_hresult;
 use windows::core::Error as WinErr;
 
+/// A failed call to a Win32 function, classified by the wrapper which made it
 #[derive(Debug, thiserror::Error)]
-#[error("Error from windows::Win32::Devices::Bluetooth::BluetoothRemoveDevice")]
-pub enum BluetoothRemoveDeviceErr {
-    InvalidMacAddress,
-    BluetoothError,
-    Other(WinErr),
+#[error("Error from {function}")]
+pub struct Win32Err {
+    pub kind: ErrorKind,
+    pub function: &'static str,
+    /// What the OS returned, unless the call succeeded with an unusable result
+    #[source]
+    pub err: Option<WinErr>,
 }
 
-pub unsafe fn bluetooth_remove_device(bluetooth_address: &BLUETOOTH_ADDRESS) -> Result<(), BluetoothRemoveDeviceErr> {
-    use BluetoothRemoveDeviceErr as Err;
-    
-    // SAFETY: caller must ensure no concurrent use? invalid addresses are handled by this function
-    match unsafe { HRESULT::from_win32(BluetoothRemoveDevice(bluetooth_address)) } {
-        res if err_eq(res, ERROR_INVALID_PARAMETER) => Err(Err::InvalidMacAddress),
-        res if err_eq(res, ERROR_NOT_FOUND) => Err(Err::InvalidMacAddress),
-        res if err_eq(res, ERROR_GEN_FAILURE) => Err(Err::BluetoothError),
-        res if err_eq(res, ERROR_INVALID_HANDLE) => Err(Err::BluetoothError),
-        res if err_eq(res, ERROR_SUCCESS) => Ok(()), // successfully removed bluetooth device
-        res => Err(Err::Other(WinErr::from_hresult(res))),
+impl Win32Err {
+    pub fn new(kind: ErrorKind, function: &'static str, h_res: HRESULT) -> Self {
+        Self {
+            kind,
+            function,
+            err: Some(WinErr::from_hresult(h_res)),
+        }
+    }
+
+    /// Whether the OS returned `win32_err`
+    pub fn is(&self, win32_err: WIN32_ERROR) -> bool {
+        self.err.as_ref().is_some_and(|err| err_eq(err.code(), win32_err))
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Error from windows::Win32::Devices::Bluetooth::BluetoothGetDeviceInfo")]
-pub enum BluetoothGetDeviceInfoErr {
-    InvalidMacAddress,
-    BluetoothError,
-    Other(WinErr),
+impl From<Win32Err> for Failure {
+    fn from(err: Win32Err) -> Self {
+        Self {
+            kind: err.kind,
+            code: err.err.as_ref().map(|err| code_of_hresult(err.code().0)),
+            source: Some(Box::new(err)),
+        }
+    }
 }
 
-pub unsafe fn bluetooth_get_device_info(h_radio: Option<HANDLE>, device_info: &mut BLUETOOTH_DEVICE_INFO) -> Result<(), BluetoothGetDeviceInfoErr> {
-    use BluetoothGetDeviceInfoErr as Err;
-    
+pub unsafe fn bluetooth_remove_device(bluetooth_address: &BLUETOOTH_ADDRESS) -> Result<(), Win32Err> {
+    // SAFETY: caller must ensure no concurrent use? invalid addresses are handled by this function
+    let h_res = HRESULT::from_win32(unsafe { BluetoothRemoveDevice(bluetooth_address) });
+
+    let kind = match h_res {
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(()), // successfully removed bluetooth device
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothRemoveDevice", h_res))
+}
+
+pub unsafe fn bluetooth_get_device_info(h_radio: Option<HANDLE>, device_info: &mut BLUETOOTH_DEVICE_INFO) -> Result<(), Win32Err> {
     let target_addr = MacAddress::from(device_info.Address);
 
     // SAFETY: dwSize should be set properly by caller, should not be used concurrently
@@ -44,14 +66,160 @@ pub unsafe fn bluetooth_get_device_info(h_radio: Option<HANDLE>, device_info: &m
 
     let res_addr = MacAddress::from(device_info.Address);
 
-    match h_res {
-        res if err_eq(res, ERROR_REVISION_MISMATCH) => unreachable!("caller should set dwSize correctly"),
-        res if err_eq(res, ERROR_INVALID_PARAMETER) => Err(Err::InvalidMacAddress),
-        res if err_eq(res, ERROR_NOT_FOUND) => Err(Err::InvalidMacAddress),
-        res if err_eq(res, ERROR_SUCCESS) && target_addr != res_addr => Err(Err::InvalidMacAddress),
-        res if err_eq(res, ERROR_GEN_FAILURE) => Err(Err::BluetoothError),
-        res if err_eq(res, ERROR_INVALID_HANDLE) => Err(Err::BluetoothError),
-        res if err_eq(res, ERROR_SUCCESS) => Ok(()), // successfully got device info
-        res => Err(Err::Other(WinErr::from_hresult(res))),
+    let kind = match h_res {
+        // the OS fell back to a different device
+        res if err_eq(res, ERROR_SUCCESS) && target_addr != res_addr => return Err(Win32Err {
+            kind: ErrorKind::InvalidMacAddress,
+            function: "BluetoothGetDeviceInfo",
+            err: None,
+        }),
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(()), // successfully got device info
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        // including ERROR_REVISION_MISMATCH, which would mean the caller set dwSize wrong
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothGetDeviceInfo", h_res))
+}
+
+/// Opens every radio; the caller must close the returned handles with `CloseHandle`
+pub unsafe fn bluetooth_find_radios() -> Result<Vec<HANDLE>, Win32Err> {
+    let find_err = |function, err: WinErr| {
+        let kind = match err.code() {
+            res if err_eq(res, ERROR_OUTOFMEMORY) => ErrorKind::OutOfMemory,
+            _ => ErrorKind::Unhandled,
+        };
+
+        Win32Err { kind, function, err: Some(err) }
+    };
+
+    let params = BLUETOOTH_FIND_RADIO_PARAMS { dwSize: size_of::<BLUETOOTH_FIND_RADIO_PARAMS>() as _ };
+    let mut h_radio = HANDLE::default();
+
+    // SAFETY: dwSize has been set correctly, h_radio is only written to
+    let h_find = match unsafe { BluetoothFindFirstRadio(&params, &mut h_radio) } {
+        Ok(h_find) => h_find,
+        Err(err) if err_eq(err.code(), ERROR_NO_MORE_ITEMS) => return Ok(Vec::new()),
+        Err(err) => return Err(find_err("BluetoothFindFirstRadio", err)),
+    };
+
+    let mut radios = vec![h_radio];
+
+    let res = loop {
+        // SAFETY: h_find is open, h_radio is only written to
+        match unsafe { BluetoothFindNextRadio(h_find, &mut h_radio) } {
+            Ok(()) => radios.push(h_radio),
+            Err(err) if err_eq(err.code(), ERROR_NO_MORE_ITEMS) => break Ok(()),
+            Err(err) => break Err(find_err("BluetoothFindNextRadio", err)),
+        }
+    };
+
+    // only the radio handles are needed, even if closing the search fails
+    // SAFETY: h_find was returned by the OS and is closed exactly once
+    let _ = unsafe { BluetoothFindRadioClose(h_find) };
+
+    match res {
+        Ok(()) => Ok(radios),
+        Err(err) => {
+            for h_radio in radios {
+                // SAFETY: the handles were opened above and aren't returned
+                let _ = unsafe { CloseHandle(h_radio) };
+            }
+
+            Err(err)
+        }
     }
-}
\ No newline at end of file
+}
+
+pub unsafe fn bluetooth_get_radio_info(h_radio: HANDLE, radio_info: &mut BLUETOOTH_RADIO_INFO) -> Result<(), Win32Err> {
+    // SAFETY: dwSize should be set properly and the handle open, by the caller
+    let h_res = HRESULT::from_win32(unsafe { BluetoothGetRadioInfo(h_radio, radio_info) });
+
+    let kind = match h_res {
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(()), // successfully got radio info
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        // including ERROR_REVISION_MISMATCH, which would mean the caller set dwSize wrong
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothGetRadioInfo", h_res))
+}
+
+pub unsafe fn bluetooth_enumerate_installed_services(h_radio: Option<HANDLE>, device_info: &BLUETOOTH_DEVICE_INFO) -> Result<Vec<GUID>, Win32Err> {
+    let mut services = Vec::new();
+
+    loop {
+        let mut count = services.len() as u32;
+        let buffer = (!services.is_empty()).then_some(services.as_mut_ptr());
+
+        // SAFETY: dwSize should be set properly by caller, buffer holds count GUIDs if it's given
+        let h_res = HRESULT::from_win32(unsafe { BluetoothEnumerateInstalledServices(h_radio, device_info, &mut count, buffer) });
+
+        let kind = match h_res {
+            // more services were installed between calls, so try again with the new count
+            res if err_eq(res, ERROR_MORE_DATA) && count as usize > services.len() => {
+                services.resize(count as usize, GUID::zeroed());
+                continue;
+            }
+            // a call without a buffer may succeed with just the count, which is then fetched with a big enough buffer
+            res if err_eq(res, ERROR_SUCCESS) && count as usize > services.len() => {
+                services.resize(count as usize, GUID::zeroed());
+                continue;
+            }
+            res if err_eq(res, ERROR_SUCCESS) => {
+                services.truncate(count as usize);
+
+                return Ok(services);
+            }
+            res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+            res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+            res if err_eq(res, ERROR_OUTOFMEMORY) => ErrorKind::OutOfMemory,
+            res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+            res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+            _ => ErrorKind::Unhandled,
+        };
+
+        return Err(Win32Err::new(kind, "BluetoothEnumerateInstalledServices", h_res));
+    }
+}
+
+/// Fails with `ERROR_SERVICE_DOES_NOT_EXIST` if the device doesn't advertise the service
+pub unsafe fn bluetooth_set_service_state(h_radio: HANDLE, device_info: &BLUETOOTH_DEVICE_INFO, service: &GUID, flags: u32) -> Result<(), Win32Err> {
+    // SAFETY: radio handle should be open and dwSize set properly by caller
+    let h_res = HRESULT::from_win32(unsafe { BluetoothSetServiceState(Some(h_radio), device_info, service, flags) });
+
+    let kind = match h_res {
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(()), // successfully changed service state
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_NOT_FOUND) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_SERVICE_DOES_NOT_EXIST) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        res if err_eq(res, ERROR_INVALID_HANDLE) => ErrorKind::BluetoothUnavailable,
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothSetServiceState", h_res))
+}
+
+/// Registers `callback` for the authentication requests of a device, returning the handle to unregister it with
+pub unsafe fn bluetooth_register_for_authentication(device_info: &BLUETOOTH_DEVICE_INFO, callback: PFN_AUTHENTICATION_CALLBACK_EX, param: *const c_void) -> Result<isize, Win32Err> {
+    let mut handle = 0;
+
+    // SAFETY: caller must ensure param stays valid until the handle is unregistered
+    let h_res = HRESULT::from_win32(unsafe { BluetoothRegisterForAuthenticationEx(Some(device_info), &mut handle, callback, Some(param)) });
+
+    let kind = match h_res {
+        res if err_eq(res, ERROR_SUCCESS) => return Ok(handle), // successfully registered
+        res if err_eq(res, ERROR_INVALID_PARAMETER) => ErrorKind::InvalidMacAddress,
+        res if err_eq(res, ERROR_OUTOFMEMORY) => ErrorKind::OutOfMemory,
+        res if err_eq(res, ERROR_GEN_FAILURE) => ErrorKind::BluetoothUnavailable,
+        _ => ErrorKind::Unhandled,
+    };
+
+    Err(Win32Err::new(kind, "BluetoothRegisterForAuthenticationEx", h_res))
+}